@@ -0,0 +1,67 @@
+use anchor_lang::prelude::*;
+
+#[error_code]
+pub enum LoopingError {
+    #[msg("Signer is neither the owner nor the delegate of the position")]
+    Unauthorized,
+    #[msg("The instruction is not wrapped in a matching Kamino flash borrow and flash repay")]
+    InvalidFlashLoan,
+    #[msg("Swap slippage is above the maximum allowed for this reserve pair")]
+    SlippageTooHigh,
+    #[msg("Swap data is not a valid Jupiter route")]
+    InvalidSwapData,
+    #[msg("Jupiter route instruction is not supported here")]
+    UnsupportedSwapRoute,
+    #[msg("Swap amount doesn't match the route amount")]
+    SwapAmountMismatch,
+    #[msg("Swap slippage doesn't match the route slippage")]
+    SlippageMismatch,
+    #[msg("Swap spent more than the quote allows")]
+    SwapInputTooHigh,
+    #[msg("Swap returned less than the quote allows")]
+    SwapOutputTooLow,
+    #[msg("Reserve price was not refreshed in this slot")]
+    StaleReservePrice,
+    #[msg("Swap price deviates from the oracle price by more than the market allows")]
+    PriceDeviationTooHigh,
+    #[msg("Reserves passed in don't match the ones the obligation uses")]
+    ObligationReserveMismatch,
+    #[msg("Not enough remaining accounts")]
+    InsufficientRemainingAccounts,
+    #[msg("Position LTV is above the maximum allowed for this market")]
+    HealthFactorTooLow,
+    #[msg("Position leverage is above the maximum allowed for this reserve pair")]
+    LeverageTooHigh,
+    #[msg("The borrow reserve account is needed but was not passed in")]
+    MissingBorrowReserve,
+    #[msg("Lending market or reserves are not allowed")]
+    MarketNotAllowed,
+    #[msg("Instruction is paused")]
+    Paused,
+    #[msg("Signer is not the admin of the config")]
+    NotAdmin,
+    #[msg("Market config already lists the maximum number of reserve pairs")]
+    ReservePairsFull,
+    #[msg("Fee is above the maximum allowed")]
+    FeeTooHigh,
+    #[msg("Route platform fee is above the maximum allowed")]
+    PlatformFeeTooHigh,
+    #[msg("The route carries a platform fee but no platform fee account was passed in")]
+    MissingPlatformFeeAccount,
+    #[msg("Position still has debt or collateral left in the obligation")]
+    PositionNotUnwound,
+    #[msg("Position is already at or above the target leverage")]
+    LeverageTargetReached,
+    #[msg("Market config limits are out of bounds")]
+    InvalidMarketConfig,
+    #[msg("Reserve pair limits are out of bounds")]
+    InvalidReservePair,
+    #[msg("Swap spent more collateral than was withdrawn for it")]
+    SwapSpentAboveWithdrawn,
+    #[msg("Swap output doesn't cover the repaid debt and the repay fee on it")]
+    RepayFeeNotCovered,
+    #[msg("Platform fee account mint is not the mint the route takes the platform fee in")]
+    PlatformFeeMintMismatch,
+    #[msg("Math overflow")]
+    MathOverflow,
+}
@@ -0,0 +1,118 @@
+use anchor_lang::prelude::*;
+use crate::jupiter::RouteKind;
+
+/// Collateral the owner deposited into the obligation.
+#[event]
+pub struct Deposited {
+    pub owner: Pubkey,
+    pub lending_market: Pubkey,
+    pub reserve: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub ltv_before_bps: u64,
+    pub ltv_after_bps: u64,
+}
+
+/// Debt borrowed, swapped to collateral and deposited back, by `looping` or by a flash loan
+/// backed `leverage`.
+#[event]
+pub struct Looped {
+    pub owner: Pubkey,
+    pub lending_market: Pubkey,
+    pub reserve_collateral: Pubkey,
+    pub reserve_borrow: Pubkey,
+    pub collateral_mint: Pubkey,
+    pub debt_mint: Pubkey,
+    pub flash_loan: bool,
+    pub route: RouteKind,
+    pub borrowed: u64,
+    /// Borrow fee paid to the treasury, in the debt mint
+    pub fee: u64,
+    /// Debt the swap spent
+    pub swapped: u64,
+    /// Collateral the swap delivered and that got deposited
+    pub deposited: u64,
+    pub ltv_before_bps: u64,
+    pub ltv_after_bps: u64,
+}
+
+/// Collateral withdrawn, swapped to debt and repaid, by `repay` or by a flash loan backed
+/// `deleverage`.
+#[event]
+pub struct Repaid {
+    pub owner: Pubkey,
+    pub lending_market: Pubkey,
+    pub reserve_collateral: Pubkey,
+    pub reserve_borrow: Pubkey,
+    pub collateral_mint: Pubkey,
+    pub debt_mint: Pubkey,
+    pub flash_loan: bool,
+    pub route: RouteKind,
+    pub withdrawn: u64,
+    /// Collateral the swap spent
+    pub swapped: u64,
+    pub repaid: u64,
+    /// Repay fee paid to the treasury, in the debt mint
+    pub fee: u64,
+    pub ltv_before_bps: u64,
+    pub ltv_after_bps: u64,
+}
+
+/// Collateral withdrawn from the obligation and sent to the owner.
+#[event]
+pub struct Withdrawn {
+    pub owner: Pubkey,
+    pub lending_market: Pubkey,
+    pub reserve: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub ltv_before_bps: u64,
+    pub ltv_after_bps: u64,
+}
+
+/// Position unwound and closed. `route` is only set when there was debt left to swap for.
+#[event]
+pub struct PositionClosed {
+    pub owner: Pubkey,
+    pub lending_market: Pubkey,
+    pub obligation: Pubkey,
+    pub collateral_mint: Pubkey,
+    pub debt_mint: Pubkey,
+    pub route: Option<RouteKind>,
+    pub withdrawn: u64,
+    /// Collateral the swap spent
+    pub swapped: u64,
+    pub repaid: u64,
+    pub ltv_before_bps: u64,
+}
+
+/// Farm reward harvested for the obligation of the position and sent to the owner.
+#[event]
+pub struct Harvested {
+    pub owner: Pubkey,
+    pub obligation: Pubkey,
+    pub farm_state: Pubkey,
+    pub reward_mint: Pubkey,
+    pub reward_index: u64,
+    pub amount: u64,
+}
+
+/// Farm reward harvested by a keeper, swapped to collateral and deposited back into the obligation.
+#[event]
+pub struct Compounded {
+    pub owner: Pubkey,
+    pub keeper: Pubkey,
+    pub lending_market: Pubkey,
+    pub reserve_collateral: Pubkey,
+    pub farm_state: Pubkey,
+    pub reward_mint: Pubkey,
+    pub collateral_mint: Pubkey,
+    pub route: RouteKind,
+    pub harvested: u64,
+    /// Tip paid to the keeper, in the collateral mint
+    pub tip: u64,
+    /// Rewards the swap spent
+    pub swapped: u64,
+    /// Collateral the swap delivered, minus the tip, that got deposited
+    pub deposited: u64,
+}
@@ -0,0 +1,26 @@
+use anchor_lang::prelude::*;
+use crate::{error::LoopingError, state::Config};
+
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info> {
+    pub pending_admin: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = pending_admin @ LoopingError::NotAdmin,
+    )]
+    pub config: Account<'info, Config>,
+}
+
+impl<'info> AcceptAdmin<'info> {
+    /// # Accept the admin handoff
+    /// 
+    /// The pending admin signs to take over, so the admin can't be handed to a wallet nobody controls.
+    pub fn accept_admin(&mut self) -> Result<()> {
+        self.config.admin = self.pending_admin.key();
+        self.config.pending_admin = Pubkey::default();
+
+        Ok(())
+    }
+}
@@ -0,0 +1,369 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{associated_token::AssociatedToken, token::{transfer, Mint, Token, TokenAccount, Transfer}};
+use crate::{constant::{JUPITER_PROGRAM_ID, KAMINO_FARMS_PROGRAM_ID, KAMINO_PROGRAM_ID}, error::LoopingError, jupiter::{check_swap_data, swap_route, SwapAccounts, SwapResult}, kamino::{check_reserve, check_swap_price, deposit_reserve_liquidity_and_obligation_collateral_v2, harvest_reward, refresh_position, refresh_reserve, DepositReserveLiquidityAndObligationCollateralV2, HarvestReward, RefreshPosition, RefreshReserve}, state::{Config, MarketConfig, Position}};
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct Compound<'info> {
+    #[account(mut)]
+    pub keeper: Signer<'info>,
+    pub owner: SystemAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"auth", owner.key().as_ref()],
+        bump,
+    )]
+    pub protocol_authority: SystemAccount<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"position",
+            owner.key().as_ref(),
+            lending_market.key().as_ref(),
+        ],
+        bump = position.bump,
+        has_one = owner,
+        constraint = reserve_reward.is_some() || position.is_owner_or_delegate(keeper.key) @ LoopingError::Unauthorized,
+        has_one = lending_market,
+        has_one = obligation,
+        has_one = reserve_collateral,
+        constraint = collateral_mint.key() == position.collateral_mint,
+    )]
+    pub position: Box<Account<'info, Position>>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused.deposit @ LoopingError::Paused,
+    )]
+    pub config: Box<Account<'info, Config>>,
+    #[account(
+        seeds = [b"market_config", lending_market.key().as_ref()],
+        bump = market_config.bump,
+        constraint = market_config.reserve_pair(&position.reserve_collateral, &position.reserve_borrow).is_ok() @ LoopingError::MarketNotAllowed,
+    )]
+    pub market_config: Box<Account<'info, MarketConfig>>,
+    pub reward_mint: Box<Account<'info, Mint>>,
+    #[account(
+        init_if_needed,
+        payer = keeper,
+        associated_token::mint = reward_mint,
+        associated_token::authority = protocol_authority,
+    )]
+    pub reward_vault: Box<Account<'info, TokenAccount>>,
+    pub collateral_mint: Box<Account<'info, Mint>>,
+    #[account(
+        mut,
+        associated_token::mint = collateral_mint,
+        associated_token::authority = protocol_authority,
+    )]
+    pub collateral_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        token::mint = collateral_mint,
+    )]
+    pub keeper_collateral_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        seeds = [b"treasury"],
+        bump,
+    )]
+    pub treasury: SystemAccount<'info>,
+    #[account(
+        mut,
+        token::authority = treasury,
+    )]
+    pub platform_fee_vault: Option<Box<Account<'info, TokenAccount>>>,
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: checked by the Kamino program
+    pub instruction_sysvar_account: UncheckedAccount<'info>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+
+    /// Kamino-specific accounts
+    #[account(
+        mut,
+        seeds = [
+            &[0],                                       // Tag
+            &[0],                                       // Id
+            protocol_authority.key().as_ref(),          // Obligation owner
+            lending_market.key().as_ref(),              // Lending market
+            Pubkey::default().as_ref(),                 // Seed1 account
+            Pubkey::default().as_ref(),                 // Seed2 account
+        ],
+        bump,
+        seeds::program = KAMINO_PROGRAM_ID,
+    )]
+    /// CHECK: checked by the Kamino program
+    pub obligation: UncheckedAccount<'info>,
+    /// CHECK: checked by the Kamino program
+    pub lending_market: UncheckedAccount<'info>,
+    #[account(
+        seeds = [
+            b"lma", 
+            lending_market.key().as_ref()
+        ],
+        bump,
+        seeds::program = KAMINO_PROGRAM_ID,
+    )]
+    /// CHECK: checked by the Kamino program
+    pub lending_market_authority: UncheckedAccount<'info>,
+    #[account(mut)]
+    /// CHECK: checked by the Kamino program
+    pub reserve_collateral: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        address = position.reserve_borrow,
+    )]
+    /// CHECK: checked by the Kamino program
+    pub reserve_borrow: Option<UncheckedAccount<'info>>,
+    /// Reserve of the same market lending the reward mint, prices the swap against the oracle
+    #[account(mut)]
+    /// CHECK: checked in `check_reserve_reward`
+    pub reserve_reward: Option<UncheckedAccount<'info>>,
+    #[account(mut)]
+    /// CHECK: checked by the Kamino program
+    pub reserve_liquidity_supply: UncheckedAccount<'info>,
+    /// CHECK: checked by the Kamino program
+    #[account(mut)]
+    pub reserve_collateral_mint: UncheckedAccount<'info>,
+    #[account(mut)]
+    /// CHECK: checked by the Kamino program
+    pub reserve_destination_deposit_collateral: UncheckedAccount<'info>,
+    /// CHECK: checked by the Kamino program
+    pub scope_oracle: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"user",
+            reserve_farm_state.key().as_ref(),
+            obligation.key().as_ref(),
+        ],
+        bump,
+        seeds::program = KAMINO_FARMS_PROGRAM_ID,
+    )]
+    /// CHECK: checked by the Kamino program
+    pub obligation_farm_state: UncheckedAccount<'info>,
+    #[account(mut)]
+    /// CHECK: checked by the Kamino program
+    pub reserve_farm_state: UncheckedAccount<'info>,
+    #[account(address = KAMINO_PROGRAM_ID)]
+    /// CHECK: checked by the Kamino program
+    pub kamino_lending_program: AccountInfo<'info>,
+    #[account(address = KAMINO_FARMS_PROGRAM_ID)]
+    /// CHECK: checked by the Kamino program
+    pub farms_program: UncheckedAccount<'info>,
+
+    /// Kamino Farms-specific accounts
+    /// CHECK: checked by the Kamino Farms program
+    pub global_config: UncheckedAccount<'info>,
+    #[account(mut)]
+    /// CHECK: checked by the Kamino Farms program
+    pub rewards_vault: UncheckedAccount<'info>,
+    #[account(mut)]
+    /// CHECK: checked by the Kamino Farms program
+    pub rewards_treasury_vault: UncheckedAccount<'info>,
+    /// CHECK: checked by the Kamino Farms program
+    pub farm_vaults_authority: UncheckedAccount<'info>,
+    /// CHECK: checked by the Kamino Farms program
+    pub scope_prices: Option<UncheckedAccount<'info>>,
+
+    /// Jupiter-specific accounts
+    /// CHECK: checked by the Jupiter program
+    pub jupiter_event_authority: AccountInfo<'info>,
+    #[account(address = JUPITER_PROGRAM_ID)]
+    /// CHECK: checked by the Jupiter program
+    pub jupiter_program: AccountInfo<'info>,
+}
+
+impl<'info> Compound<'info> {
+    /// # Check the reward reserve
+    /// 
+    /// Without an oracle price the swap is only bounded by the quote the keeper brings, so anyone 
+    /// but the owner and its delegate has to pass the reserve lending the reward mint in this market.
+    pub fn check_reserve_reward(&self) -> Result<()> {
+        let Some(reserve_reward) = &self.reserve_reward else {
+            return Ok(());
+        };
+
+        check_reserve(reserve_reward, &self.lending_market.key(), &self.reward_mint.key())
+    }
+
+    /// # Refresh the position
+    /// 
+    /// Both reserves, any other reserve the obligation uses and then the obligation, see `refresh_position`.
+    pub fn refresh_position(&self, reserves: &[AccountInfo<'info>]) -> Result<()> {
+        refresh_position(RefreshPosition {
+            obligation: self.obligation.to_account_info(),
+            lending_market: self.lending_market.to_account_info(),
+            reserve_collateral: self.reserve_collateral.to_account_info(),
+            reserve_borrow: self.reserve_borrow.as_ref().map(|reserve_borrow| reserve_borrow.to_account_info()),
+            scope_oracle: self.scope_oracle.to_account_info(),
+            kamino_lending_program: self.kamino_lending_program.to_account_info(),
+            reserves,
+        })
+    }
+
+    /// # Refresh the reward reserve
+    /// 
+    /// Needed in the same slot as the swap so its price is the one Kamino just pulled from the oracle.
+    pub fn refresh_reserve_reward(&mut self) -> Result<()> {
+        let Some(reserve_reward) = &self.reserve_reward else {
+            return Ok(());
+        };
+
+        refresh_reserve(RefreshReserve {
+            reserve: reserve_reward.to_account_info(),
+            lending_market: self.lending_market.to_account_info(),
+            scope_oracle: self.scope_oracle.to_account_info(),
+            kamino_lending_program: self.kamino_lending_program.to_account_info(),
+        })
+    }
+
+    /// # Harvest a reward of the collateral farm
+    /// 
+    /// The reward stays in the reward vault of the protocol authority to be swapped. Returns the 
+    /// amount harvested.
+    pub fn harvest(&mut self, reward_index: u64, bumps: &CompoundBumps) -> Result<u64> {
+        let owner_key = self.owner.key();
+        let signer_seeds: [&[&[u8]];1] = [&[
+            b"auth".as_ref(),
+            owner_key.as_ref(),
+            &[bumps.protocol_authority]
+        ]];
+
+        let balance_before = self.reward_vault.amount;
+
+        harvest_reward(
+            HarvestReward {
+                owner: self.protocol_authority.to_account_info(),
+                user_state: self.obligation_farm_state.to_account_info(),
+                farm_state: self.reserve_farm_state.to_account_info(),
+                global_config: self.global_config.to_account_info(),
+                reward_mint: self.reward_mint.to_account_info(),
+                user_reward_ata: self.reward_vault.to_account_info(),
+                rewards_vault: self.rewards_vault.to_account_info(),
+                rewards_treasury_vault: self.rewards_treasury_vault.to_account_info(),
+                farm_vaults_authority: self.farm_vaults_authority.to_account_info(),
+                scope_prices: self.scope_prices.as_ref().map(|scope_prices| scope_prices.to_account_info()),
+                token_program: self.token_program.to_account_info(),
+                farms_program: self.farms_program.to_account_info(),
+            },
+            reward_index,
+            &signer_seeds,
+        )?;
+
+        self.reward_vault.reload()?;
+
+        self.reward_vault.amount.checked_sub(balance_before).ok_or(error!(LoopingError::MathOverflow))
+    }
+
+    /// # Pay the keeper tip
+    /// 
+    /// Paid out of the collateral the swap delivered, before it gets deposited.
+    pub fn pay_tip(&mut self, tip: u64, bumps: &CompoundBumps) -> Result<()> {
+        if tip == 0 {
+            return Ok(());
+        }
+
+        let owner_key = self.owner.key();
+        let signer_seeds: [&[&[u8]];1] = [&[
+            b"auth".as_ref(),
+            owner_key.as_ref(),
+            &[bumps.protocol_authority]
+        ]];
+
+        let cpi_accounts = Transfer {
+            from: self.collateral_vault.to_account_info(),
+            to: self.keeper_collateral_account.to_account_info(),
+            authority: self.protocol_authority.to_account_info(),
+        };
+
+        transfer(
+            CpiContext::new_with_signer(self.token_program.to_account_info(), cpi_accounts, &signer_seeds),
+            tip,
+        )
+    }
+
+    /// # Swap the rewards to collateral
+    /// 
+    /// The keeper picks the amount since the harvest can't be predicted to the unit, what it leaves in
+    /// the reward vault gets swapped by a later compound. The slippage is bounded by the reward swap
+    /// maximum of the config, not by the one of the reserve pair.
+    pub fn swap_rewards(&mut self, swap_data: &[u8], amount: u64, slippage_bps: u16, remaining_accounts: &[AccountInfo<'info>], bumps: &CompoundBumps) -> Result<SwapResult> {
+        // Perform a discriminator, amount and slippage check
+        let route = check_swap_data(swap_data, amount, slippage_bps, self.config.max_reward_slippage_bps, self.config.max_platform_fee_bps)?;
+
+        // Jupiter skims the platform fee into the treasury, in the output mint of exact-in routes and the input mint of exact-out ones
+        let platform_fee_account = match route.platform_fee_bps {
+            0 => None,
+            _ => Some(self.platform_fee_vault.as_ref().ok_or(error!(LoopingError::MissingPlatformFeeAccount))?.to_account_info()),
+        };
+
+        let owner_key = self.owner.key();
+        let signer_seeds: [&[&[u8]];1] = [&[
+            b"auth".as_ref(),
+            owner_key.as_ref(),
+            &[bumps.protocol_authority]
+        ]];
+
+        let accounts = SwapAccounts {
+            token_program: self.token_program.to_account_info(),
+            user_transfer_authority: self.protocol_authority.to_account_info(),
+            source_token_account: self.reward_vault.to_account_info(),
+            destination_token_account: self.collateral_vault.to_account_info(),
+            source_mint: self.reward_mint.to_account_info(),
+            destination_mint: self.collateral_mint.to_account_info(),
+            event_authority: self.jupiter_event_authority.to_account_info(),
+            jupiter_program: self.jupiter_program.to_account_info(),
+            platform_fee_account,
+        };
+
+        let swap = swap_route(accounts, &route, swap_data, remaining_accounts, &signer_seeds)?;
+        if let Some(reserve_reward) = &self.reserve_reward {
+            check_swap_price(reserve_reward, &self.reserve_collateral, swap.spent, swap.received, self.market_config.max_price_deviation_bps)?;
+        }
+
+        Ok(swap)
+    }
+
+    /// # Deposit the swapped collateral
+    /// 
+    /// Only `amount`, what the swap delivered minus the keeper tip, goes in: anything else sitting in the
+    /// vault stays there.
+    pub fn deposit(&mut self, amount: u64, bumps: &CompoundBumps) -> Result<()> {
+        let owner_key = self.owner.key();
+        let signer_seeds: [&[&[u8]];1] = [&[
+            b"auth".as_ref(),
+            owner_key.as_ref(),
+            &[bumps.protocol_authority]
+        ]];
+
+        deposit_reserve_liquidity_and_obligation_collateral_v2(
+            DepositReserveLiquidityAndObligationCollateralV2 {
+                owner: self.protocol_authority.to_account_info(),
+                obligation: self.obligation.to_account_info(),
+                lending_market: self.lending_market.to_account_info(),
+                lending_market_authority: self.lending_market_authority.to_account_info(),
+                reserve: self.reserve_collateral.to_account_info(),
+                reserve_liquidity_mint: self.collateral_mint.to_account_info(),
+                reserve_liquidity_supply: self.reserve_liquidity_supply.to_account_info(),
+                reserve_collateral_mint: self.reserve_collateral_mint.to_account_info(),
+                reserve_destination_deposit_collateral: self.reserve_destination_deposit_collateral.to_account_info(),
+                user_source_liquidity: self.collateral_vault.to_account_info(),
+                token_program: self.token_program.to_account_info(),
+                instruction_sysvar_account: self.instruction_sysvar_account.to_account_info(),
+                obligation_farm_user_state: Some(self.obligation_farm_state.to_account_info()),
+                reserve_farm_state: Some(self.reserve_farm_state.to_account_info()),
+                farms_program: self.farms_program.to_account_info(),
+                kamino_lending_program: self.kamino_lending_program.to_account_info(),
+            },
+            amount,
+            &signer_seeds,
+        )?;
+
+        self.position.deposited = self.position.deposited.checked_add(amount).ok_or(error!(LoopingError::MathOverflow))?;
+
+        Ok(())
+    }
+}
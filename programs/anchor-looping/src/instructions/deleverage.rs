@@ -0,0 +1,427 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{transfer, Mint, Token, TokenAccount, Transfer};
+use crate::{constant::{JUPITER_PROGRAM_ID, KAMINO_PROGRAM_ID}, error::LoopingError, jupiter::{check_exact_out_swap_data, swap_route, SwapAccounts, SwapResult}, kamino::{check_flash_loan, check_swap_price, deposit_reserve_liquidity_and_obligation_collateral_v2, refresh_position, repay_obligation_liquidity_v2, withdraw_obligation_collateral_and_redeem_reserve_collateral_v2, DepositReserveLiquidityAndObligationCollateralV2, RefreshPosition, RepayObligationLiquidityV2, WithdrawObligationCollateralAndRedeemReserveCollateralV2}, state::{Config, MarketConfig, Position}};
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct Deleverage<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"auth", owner.key().as_ref()],
+        bump,
+    )]
+    pub protocol_authority: SystemAccount<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"position",
+            owner.key().as_ref(),
+            lending_market.key().as_ref(),
+        ],
+        bump = position.bump,
+        has_one = owner,
+        has_one = lending_market,
+        has_one = obligation,
+        has_one = reserve_collateral,
+        has_one = reserve_borrow,
+        constraint = input_mint.key() == position.collateral_mint,
+        constraint = output_mint.key() == position.debt_mint,
+    )]
+    pub position: Box<Account<'info, Position>>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused.repay @ LoopingError::Paused,
+    )]
+    pub config: Box<Account<'info, Config>>,
+    #[account(
+        seeds = [b"market_config", lending_market.key().as_ref()],
+        bump = market_config.bump,
+        constraint = market_config.reserve_pair(&position.reserve_collateral, &position.reserve_borrow).is_ok() @ LoopingError::MarketNotAllowed,
+    )]
+    pub market_config: Box<Account<'info, MarketConfig>>,
+    pub input_mint: Box<Account<'info, Mint>>,
+    #[account(
+        mut,
+        associated_token::mint = input_mint,
+        associated_token::authority = protocol_authority,
+    )]
+    pub input_vault: Box<Account<'info, TokenAccount>>,
+    pub output_mint: Box<Account<'info, Mint>>,
+    #[account(
+        mut,
+        associated_token::mint = output_mint,
+        associated_token::authority = protocol_authority,
+    )]
+    pub output_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        token::mint = output_mint,
+        token::authority = owner,
+    )]
+    pub owner_debt_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        seeds = [b"treasury"],
+        bump,
+    )]
+    pub treasury: SystemAccount<'info>,
+    #[account(
+        mut,
+        associated_token::mint = output_mint,
+        associated_token::authority = treasury,
+    )]
+    pub treasury_vault: Box<Account<'info, TokenAccount>>,
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: checked by the Kamino program and by the flash loan check
+    pub instruction_sysvar_account: UncheckedAccount<'info>,
+    pub token_program: Program<'info, Token>,
+
+    /// Kamino-specific accounts
+    #[account(
+        mut,
+        seeds = [
+            b"user_meta", 
+            protocol_authority.key().as_ref()           // Owner of the user metadata
+        ],
+        bump,
+        seeds::program = KAMINO_PROGRAM_ID,
+    )]
+    /// CHECK: checked by the Kamino program
+    pub user_metadata: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [
+            &[0],                                       // Tag
+            &[0],                                       // Id
+            protocol_authority.key().as_ref(),          // Obligation owner
+            lending_market.key().as_ref(),              // Lending market
+            Pubkey::default().as_ref(),                 // Seed1 account
+            Pubkey::default().as_ref(),                 // Seed2 account
+        ],
+        bump,
+        seeds::program = KAMINO_PROGRAM_ID,
+    )]
+    /// CHECK: checked by the Kamino program
+    pub obligation: UncheckedAccount<'info>,
+    /// CHECK: checked by the Kamino program
+    pub lending_market: UncheckedAccount<'info>,
+    #[account(
+        seeds = [
+            b"lma", 
+            lending_market.key().as_ref()
+        ],
+        bump,
+        seeds::program = KAMINO_PROGRAM_ID,
+    )]
+    /// CHECK: checked by the Kamino program
+    pub lending_market_authority: UncheckedAccount<'info>,
+    #[account(mut)]
+    /// CHECK: checked by the Kamino program
+    pub reserve_collateral: UncheckedAccount<'info>,
+    #[account(mut)]
+    /// CHECK: checked by the Kamino program
+    pub reserve_liquidity_supply: UncheckedAccount<'info>,
+    /// CHECK: checked by the Kamino program
+    #[account(mut)]
+    pub reserve_collateral_mint: UncheckedAccount<'info>,
+    #[account(mut)]
+    /// CHECK: checked by the Kamino program
+    pub reserve_source_collateral: UncheckedAccount<'info>,
+    #[account(mut)]
+    /// CHECK: checked by the Kamino program
+    pub reserve_borrow: UncheckedAccount<'info>,
+    #[account(mut)]
+    /// CHECK: checked by the Kamino program
+    pub borrow_reserve_destination_liquidity: UncheckedAccount<'info>,
+    /// CHECK: checked by the Kamino program
+    pub scope_oracle: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"user",
+            reserve_farm_state.key().as_ref(),
+            obligation.key().as_ref(),
+        ],
+        bump,
+        seeds::program = farms_program.key(),
+    )]
+    /// CHECK: checked by the Kamino program
+    pub obligation_farm_state: UncheckedAccount<'info>,
+    #[account(mut)]
+    /// CHECK: checked by the Kamino program
+    pub reserve_farm_state: UncheckedAccount<'info>,
+    #[account(address = KAMINO_PROGRAM_ID)]
+    /// CHECK: checked by the Kamino program
+    pub kamino_lending_program: AccountInfo<'info>,
+    /// CHECK: checked by the Kamino program
+    pub farms_program: UncheckedAccount<'info>,
+
+    /// Jupiter-specific accounts
+    /// CHECK: checked by the Jupiter program
+    pub jupiter_event_authority: AccountInfo<'info>,
+    #[account(address = JUPITER_PROGRAM_ID)]
+    /// CHECK: checked by the Jupiter program
+    pub jupiter_program: AccountInfo<'info>,
+
+}
+
+impl<'info> Deleverage<'info> {
+    /// # Check the flash loan
+    /// 
+    /// The flash loan has to be borrowed from the debt reserve into the owner debt account right before
+    /// this instruction and repaid from the same account right after it.
+    pub fn check_flash_loan(&self, amount: u64) -> Result<()> {
+        let flash_amount = check_flash_loan(
+            &self.instruction_sysvar_account.to_account_info(),
+            &self.reserve_borrow.key(),
+            &self.owner_debt_account.key(),
+            amount,
+        )?;
+        require_eq!(flash_amount, amount, LoopingError::InvalidFlashLoan);
+
+        Ok(())
+    }
+
+    /// # Move the flash loan into the vault
+    pub fn transfer_from_owner(&mut self, amount: u64) -> Result<()> {
+        let cpi_accounts = Transfer {
+            from: self.owner_debt_account.to_account_info(),
+            to: self.output_vault.to_account_info(),
+            authority: self.owner.to_account_info(),
+        };
+
+        transfer(
+            CpiContext::new(self.token_program.to_account_info(), cpi_accounts),
+            amount,
+        )
+    }
+
+    /// # Refresh the position
+    /// 
+    /// Both reserves, any other reserve the obligation uses and then the obligation, see `refresh_position`.
+    pub fn refresh_position(&self, reserves: &[AccountInfo<'info>]) -> Result<()> {
+        refresh_position(RefreshPosition {
+            obligation: self.obligation.to_account_info(),
+            lending_market: self.lending_market.to_account_info(),
+            reserve_collateral: self.reserve_collateral.to_account_info(),
+            reserve_borrow: Some(self.reserve_borrow.to_account_info()),
+            scope_oracle: self.scope_oracle.to_account_info(),
+            kamino_lending_program: self.kamino_lending_program.to_account_info(),
+            reserves,
+        })
+    }
+
+    pub fn withdraw_collateral(&mut self, amount: u64, bumps: &DeleverageBumps) -> Result<u64> {
+        let owner_key = self.owner.key();
+        let signer_seeds: [&[&[u8]];1] = [&[
+            b"auth".as_ref(),
+            owner_key.as_ref(),
+            &[bumps.protocol_authority]
+        ]];
+
+        let balance_before = self.input_vault.amount;
+
+        withdraw_obligation_collateral_and_redeem_reserve_collateral_v2(
+            WithdrawObligationCollateralAndRedeemReserveCollateralV2 {
+                owner: self.protocol_authority.to_account_info(),
+                obligation: self.obligation.to_account_info(),
+                lending_market: self.lending_market.to_account_info(),
+                lending_market_authority: self.lending_market_authority.to_account_info(),
+                withdraw_reserve: self.reserve_collateral.to_account_info(),
+                reserve_liquidity_mint: self.input_mint.to_account_info(),
+                reserve_source_collateral: self.reserve_source_collateral.to_account_info(),
+                reserve_collateral_mint: self.reserve_collateral_mint.to_account_info(),
+                reserve_liquidity_supply: self.reserve_liquidity_supply.to_account_info(),
+                user_destination_liquidity: self.input_vault.to_account_info(),
+                token_program: self.token_program.to_account_info(),
+                instruction_sysvar_account: self.instruction_sysvar_account.to_account_info(),
+                obligation_farm_user_state: Some(self.obligation_farm_state.to_account_info()),
+                reserve_farm_state: Some(self.reserve_farm_state.to_account_info()),
+                farms_program: self.farms_program.to_account_info(),
+                kamino_lending_program: self.kamino_lending_program.to_account_info(),
+            },
+            amount,
+            &signer_seeds,
+        )?;
+
+        self.input_vault.reload()?;
+        let withdrawn = self.input_vault.amount.checked_sub(balance_before).ok_or(error!(LoopingError::MathOverflow))?;
+        self.position.withdrawn = self.position.withdrawn.checked_add(withdrawn).ok_or(error!(LoopingError::MathOverflow))?;
+
+        Ok(withdrawn)
+    }
+
+    /// # Swap the collateral back to debt
+    /// 
+    /// The route has to be exact-out and deliver at least `min_amount`, what was repaid and the fee on it.
+    /// The client can't predict the interest a full repay covers, so the route can aim above it: the surplus
+    /// goes back with the flash repay.
+    pub fn swap_for_debt(&mut self, swap_data: &[u8], min_amount: u64, slippage_bps: u16, remaining_accounts: &[AccountInfo<'info>], bumps: &DeleverageBumps) -> Result<SwapResult> {
+        // Perform a discriminator, amount and slippage check, the flash loan swap pays no platform fee
+        let reserve_pair = self.market_config.reserve_pair(&self.position.reserve_collateral, &self.position.reserve_borrow)?;
+        let route = check_exact_out_swap_data(swap_data, min_amount, slippage_bps, reserve_pair.max_slippage_bps, 0)?;
+
+        let owner_key = self.owner.key();
+        let signer_seeds: [&[&[u8]];1] = [&[
+            b"auth".as_ref(),
+            owner_key.as_ref(),
+            &[bumps.protocol_authority]
+        ]];
+
+        let accounts = SwapAccounts {
+            token_program: self.token_program.to_account_info(),
+            user_transfer_authority: self.protocol_authority.to_account_info(),
+            source_token_account: self.input_vault.to_account_info(),
+            destination_token_account: self.output_vault.to_account_info(),
+            source_mint: self.input_mint.to_account_info(),
+            destination_mint: self.output_mint.to_account_info(),
+            event_authority: self.jupiter_event_authority.to_account_info(),
+            jupiter_program: self.jupiter_program.to_account_info(),
+            platform_fee_account: None,
+        };
+
+        let swap = swap_route(accounts, &route, swap_data, remaining_accounts, &signer_seeds)?;
+        check_swap_price(&self.reserve_collateral, &self.reserve_borrow, swap.spent, swap.received, self.market_config.max_price_deviation_bps)?;
+
+        Ok(swap)
+    }
+
+    /// # Repay the debt
+    /// 
+    /// `u64::MAX` repays the whole debt. Returns the amount actually repaid.
+    pub fn repay_debt(&mut self, amount: u64, bumps: &DeleverageBumps) -> Result<u64> {
+        let owner_key = self.owner.key();
+        let signer_seeds: [&[&[u8]];1] = [&[
+            b"auth".as_ref(),
+            owner_key.as_ref(),
+            &[bumps.protocol_authority]
+        ]];
+
+        self.output_vault.reload()?;
+        let balance_before = self.output_vault.amount;
+
+        repay_obligation_liquidity_v2(
+            RepayObligationLiquidityV2 {
+                owner: self.protocol_authority.to_account_info(),
+                obligation: self.obligation.to_account_info(),
+                lending_market: self.lending_market.to_account_info(),
+                repay_reserve: self.reserve_borrow.to_account_info(),
+                reserve_liquidity_mint: self.output_mint.to_account_info(),
+                reserve_destination_liquidity: self.borrow_reserve_destination_liquidity.to_account_info(),
+                user_source_liquidity: self.output_vault.to_account_info(),
+                token_program: self.token_program.to_account_info(),
+                instruction_sysvar_account: self.instruction_sysvar_account.to_account_info(),
+                obligation_farm_user_state: None,
+                reserve_farm_state: None,
+                lending_market_authority: self.lending_market_authority.to_account_info(),
+                farms_program: self.farms_program.to_account_info(),
+                kamino_lending_program: self.kamino_lending_program.to_account_info(),
+            },
+            amount,
+            &signer_seeds,
+        )?;
+
+        self.output_vault.reload()?;
+        let repaid = balance_before.checked_sub(self.output_vault.amount).ok_or(error!(LoopingError::MathOverflow))?;
+        self.position.repaid = self.position.repaid.checked_add(repaid).ok_or(error!(LoopingError::MathOverflow))?;
+
+        Ok(repaid)
+    }
+
+    /// # Put the unswapped collateral back
+    /// 
+    /// The exact-out route rarely spends all the collateral we withdrew for it. What it left in the vault
+    /// goes back into the obligation instead of sitting there.
+    pub fn redeposit(&mut self, amount: u64, bumps: &DeleverageBumps) -> Result<()> {
+        if amount == 0 {
+            return Ok(());
+        }
+
+        let owner_key = self.owner.key();
+        let signer_seeds: [&[&[u8]];1] = [&[
+            b"auth".as_ref(),
+            owner_key.as_ref(),
+            &[bumps.protocol_authority]
+        ]];
+
+        deposit_reserve_liquidity_and_obligation_collateral_v2(
+            DepositReserveLiquidityAndObligationCollateralV2 {
+                owner: self.protocol_authority.to_account_info(),
+                obligation: self.obligation.to_account_info(),
+                lending_market: self.lending_market.to_account_info(),
+                lending_market_authority: self.lending_market_authority.to_account_info(),
+                reserve: self.reserve_collateral.to_account_info(),
+                reserve_liquidity_mint: self.input_mint.to_account_info(),
+                reserve_liquidity_supply: self.reserve_liquidity_supply.to_account_info(),
+                reserve_collateral_mint: self.reserve_collateral_mint.to_account_info(),
+                reserve_destination_deposit_collateral: self.reserve_source_collateral.to_account_info(),
+                user_source_liquidity: self.input_vault.to_account_info(),
+                token_program: self.token_program.to_account_info(),
+                instruction_sysvar_account: self.instruction_sysvar_account.to_account_info(),
+                obligation_farm_user_state: Some(self.obligation_farm_state.to_account_info()),
+                reserve_farm_state: Some(self.reserve_farm_state.to_account_info()),
+                farms_program: self.farms_program.to_account_info(),
+                kamino_lending_program: self.kamino_lending_program.to_account_info(),
+            },
+            amount,
+            &signer_seeds,
+        )?;
+
+        self.position.deposited = self.position.deposited.checked_add(amount).ok_or(error!(LoopingError::MathOverflow))?;
+
+        Ok(())
+    }
+
+    /// # Send the debt liquidity back to the owner
+    /// 
+    /// This is what the flash repay that follows our instruction pulls from: the swap output plus
+    /// whatever the flash loan had left after repaying the debt.
+    pub fn transfer_to_owner(&mut self, amount: u64, bumps: &DeleverageBumps) -> Result<()> {
+        let owner_key = self.owner.key();
+        let signer_seeds: [&[&[u8]];1] = [&[
+            b"auth".as_ref(),
+            owner_key.as_ref(),
+            &[bumps.protocol_authority]
+        ]];
+
+        let cpi_accounts = Transfer {
+            from: self.output_vault.to_account_info(),
+            to: self.owner_debt_account.to_account_info(),
+            authority: self.protocol_authority.to_account_info(),
+        };
+
+        transfer(
+            CpiContext::new_with_signer(self.token_program.to_account_info(), cpi_accounts, &signer_seeds),
+            amount,
+        )
+    }
+
+    /// # Pay the repay fee to the treasury
+    pub fn pay_fee(&mut self, fee: u64, bumps: &DeleverageBumps) -> Result<()> {
+        if fee == 0 {
+            return Ok(());
+        }
+
+        let owner_key = self.owner.key();
+        let signer_seeds: [&[&[u8]];1] = [&[
+            b"auth".as_ref(),
+            owner_key.as_ref(),
+            &[bumps.protocol_authority]
+        ]];
+
+        let cpi_accounts = Transfer {
+            from: self.output_vault.to_account_info(),
+            to: self.treasury_vault.to_account_info(),
+            authority: self.protocol_authority.to_account_info(),
+        };
+
+        transfer(
+            CpiContext::new_with_signer(self.token_program.to_account_info(), cpi_accounts, &signer_seeds),
+            fee,
+        )
+    }
+}
@@ -1,33 +1,67 @@
-use anchor_lang::{prelude::*, solana_program::{instruction::Instruction, program::{invoke, invoke_signed}}};
-use anchor_spl::token::{Mint, Token, TokenAccount};
-use crate::constant::{FLAG_HAS_BORROWS, FLAG_HAS_COLLATERAL, KAMINO_PROGRAM_ID, PROTOCOL_AUTHORITY_BUMP};
-
-const REFRESH_RESERVE_DISCRIMINATOR: [u8; 8] = [2, 218, 138, 235, 79, 201, 25, 102];
-const REFRESH_OBLIGATION_DISCRIMINATOR: [u8; 8] = [33, 132, 147, 228, 151, 192, 72, 89];
-const DEPOSIT_RESERVE_LIQUIDITY_AND_OBLIGATION_COLLATERAL_V2_DISCRIMINATOR: [u8; 8] = [216, 224, 191, 27, 204, 151, 102, 175];
+use anchor_lang::prelude::*;
+use anchor_spl::{associated_token::AssociatedToken, token::{transfer, Mint, Token, TokenAccount, Transfer}};
+use crate::{constant::KAMINO_PROGRAM_ID, error::LoopingError, kamino::{deposit_reserve_liquidity_and_obligation_collateral_v2, refresh_position, DepositReserveLiquidityAndObligationCollateralV2, RefreshPosition}, state::{Config, MarketConfig, Position}};
 
+#[event_cpi]
 #[derive(Accounts)]
 pub struct Deposit<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
+    pub owner: Signer<'info>,
     #[account(
         mut,
-        seeds = [b"auth"],
+        seeds = [b"auth", owner.key().as_ref()],
         bump,
     )]
     pub protocol_authority: SystemAccount<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"position",
+            owner.key().as_ref(),
+            lending_market.key().as_ref(),
+        ],
+        bump = position.bump,
+        has_one = owner,
+        has_one = lending_market,
+        has_one = obligation,
+        has_one = reserve_collateral,
+        constraint = reserve_liquidity_mint.key() == position.collateral_mint,
+    )]
+    pub position: Account<'info, Position>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused.deposit @ LoopingError::Paused,
+    )]
+    pub config: Box<Account<'info, Config>>,
+    #[account(
+        seeds = [b"market_config", lending_market.key().as_ref()],
+        bump = market_config.bump,
+        constraint = market_config.reserve_pair(&position.reserve_collateral, &position.reserve_borrow).is_ok() @ LoopingError::MarketNotAllowed,
+    )]
+    pub market_config: Box<Account<'info, MarketConfig>>,
     pub reserve_liquidity_mint: Account<'info, Mint>,
     #[account(
         mut,
+        token::mint = reserve_liquidity_mint,
+        token::authority = owner,
+    )]
+    pub user_source_liquidity: Account<'info, TokenAccount>,
+    #[account(
+        init_if_needed,
+        payer = payer,
         associated_token::mint = reserve_liquidity_mint,
         associated_token::authority = protocol_authority,
     )]
-    pub user_source_liquidity: Account<'info, TokenAccount>,
+    pub vault: Account<'info, TokenAccount>,
 
     #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
     /// CHECK: checked by the Kamino program
     pub instruction_sysvar_account: UncheckedAccount<'info>,
     pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
 
     /// Kamino-specific accounts
     #[account(
@@ -71,7 +105,10 @@ pub struct Deposit<'info> {
     #[account(mut)]
     /// CHECK: checked by the Kamino program
     pub reserve_collateral: UncheckedAccount<'info>,
-    #[account(mut)]
+    #[account(
+        mut,
+        address = position.reserve_borrow,
+    )]
     /// CHECK: checked by the Kamino program
     pub reserve_borrow: Option<UncheckedAccount<'info>>,
     #[account(mut)]
@@ -108,198 +145,71 @@ pub struct Deposit<'info> {
 }
 
 impl<'info> Deposit<'info> {
-    /// # Refresh the reserve collateral
-    /// 
-    /// This is a step needed to refresh the reserve collateral before interacting with it.
-    /// 
-    /// We are going to only use scope as oracle (this is Kamino in-house oracle that doesn't require any crank
-    /// since they do it for you)
-    pub fn refresh_reserve_collateral(&mut self) -> Result<()> {
-        let accounts = vec![
-            AccountMeta::new(self.reserve_collateral.key(), false),                 // reserve
-            AccountMeta::new_readonly(self.lending_market.key(), false),            // lending_market
-            AccountMeta::new_readonly(self.kamino_lending_program.key(), false),    // [optional] pyth_oracle
-            AccountMeta::new_readonly(self.kamino_lending_program.key(), false),    // [optional] switchboard_price_oracle
-            AccountMeta::new_readonly(self.kamino_lending_program.key(), false),    // [optional] switchboard_twap_oracle
-            AccountMeta::new_readonly(self.scope_oracle.key(), false),              // [optional] scope_oracle
-        ];
-        let account_infos = vec![
-            self.reserve_collateral.to_account_info(),
-            self.lending_market.to_account_info(),
-            self.kamino_lending_program.to_account_info(),
-            self.scope_oracle.to_account_info(),
-        ];
-
-        let refresh_reserve_collateral_ix = Instruction {
-            program_id: self.kamino_lending_program.key(),
-            accounts,
-            data: vec![
-                REFRESH_RESERVE_DISCRIMINATOR.as_ref(),
-            ].concat(),
-        };
-
-        invoke(
-            &refresh_reserve_collateral_ix,
-            &account_infos,
-        )?;
-
-        Ok(())
-    }
-
-    /// # Refresh the borrow collateral
+    /// # Refresh the position
     /// 
-    /// This is a step needed to refresh the borrow collateral before interacting with it.
-    /// 
-    /// We are going to only use scope as oracle (this is Kamino in-house oracle that doesn't require any crank
-    /// since they do it for you)
-    /// 
-    /// Note: This is a step needed only if the obligation has any borrows.
-    pub fn refresh_reserve_borrow(&mut self) -> Result<()> {
-        if let Some(reserve_borrow) = &self.reserve_borrow {
-            let accounts = vec![
-                AccountMeta::new(reserve_borrow.key(), false),                          // reserve
-                AccountMeta::new_readonly(self.lending_market.key(), false),            // lending_market
-                AccountMeta::new_readonly(self.kamino_lending_program.key(), false),    // [optional] pyth_oracle
-                AccountMeta::new_readonly(self.kamino_lending_program.key(), false),    // [optional] switchboard_price_oracle
-                AccountMeta::new_readonly(self.kamino_lending_program.key(), false),    // [optional] switchboard_twap_oracle
-                AccountMeta::new_readonly(self.scope_oracle.key(), false),              // [optional] scope_oracle
-            ];
-            let account_infos = vec![
-                reserve_borrow.to_account_info(),
-                self.lending_market.to_account_info(),
-                self.kamino_lending_program.to_account_info(),
-                self.scope_oracle.to_account_info(),
-            ];
-
-            let refresh_reserve_collateral_ix = Instruction {
-                program_id: self.kamino_lending_program.key(),
-                accounts,
-                data: vec![
-                    REFRESH_RESERVE_DISCRIMINATOR.as_ref(),
-                ].concat(),
-            };
-
-            invoke(
-                &refresh_reserve_collateral_ix,
-                &account_infos,
-            )?;
-
-            Ok(())
-        } else {
-            return Err(ProgramError::InvalidInstructionData.into());
-        }
+    /// Both reserves, any other reserve the obligation uses and then the obligation, see `refresh_position`.
+    pub fn refresh_position(&self, reserves: &[AccountInfo<'info>]) -> Result<()> {
+        refresh_position(RefreshPosition {
+            obligation: self.obligation.to_account_info(),
+            lending_market: self.lending_market.to_account_info(),
+            reserve_collateral: self.reserve_collateral.to_account_info(),
+            reserve_borrow: self.reserve_borrow.as_ref().map(|reserve_borrow| reserve_borrow.to_account_info()),
+            scope_oracle: self.scope_oracle.to_account_info(),
+            kamino_lending_program: self.kamino_lending_program.to_account_info(),
+            reserves,
+        })
     }
 
-    /// # Refresh the obligation
+    /// # Pull the tokens from the owner
     /// 
-    /// This is a step needed to refresh the obligation before interacting with it.
-    /// 
-    /// Note: We need to supply as remaining account any cranked reserve account that is used in the obligation for
-    /// both collateral and borrows.
-    pub fn refresh_obligation(&mut self, flags: u8) -> Result<()> {
-        let mut accounts = vec![
-            AccountMeta::new_readonly(self.lending_market.key(), false),            // lending_market
-            AccountMeta::new(self.obligation.key(), false),                         // obligation
-        ];
-        if flags & FLAG_HAS_COLLATERAL != 0 {
-            accounts.push(AccountMeta::new_readonly(self.reserve_collateral.key(), false));
-        }
-        if flags & FLAG_HAS_BORROWS != 0 {
-            if let Some(reserve_borrow) = &self.reserve_borrow {
-                accounts.push(AccountMeta::new_readonly(reserve_borrow.key(), false));
-            }
-        }
-
-        let mut account_infos = vec![
-            self.lending_market.to_account_info(),
-            self.obligation.to_account_info(),
-        ];
-        if flags & FLAG_HAS_COLLATERAL != 0 {
-            account_infos.push(self.reserve_collateral.to_account_info());
-        }
-        if flags & FLAG_HAS_BORROWS != 0 {
-            if let Some(reserve_borrow) = &self.reserve_borrow {
-                account_infos.push(reserve_borrow.to_account_info());
-            }
-        }
-
-        let refresh_obligation_ix = Instruction {
-            program_id: self.kamino_lending_program.key(),
-            accounts,
-            data: vec![
-                REFRESH_OBLIGATION_DISCRIMINATOR.as_ref(),
-            ].concat(),
+    /// Kamino only accepts liquidity from a token account owned by the obligation owner, so the
+    /// amount is moved from the owner's wallet into the protocol authority vault first.
+    pub fn transfer_from_owner(&mut self, amount: u64) -> Result<()> {
+        let cpi_accounts = Transfer {
+            from: self.user_source_liquidity.to_account_info(),
+            to: self.vault.to_account_info(),
+            authority: self.owner.to_account_info(),
         };
 
-        invoke(
-            &refresh_obligation_ix,
-            &account_infos,
-        )?;
-
-        Ok(())
+        transfer(
+            CpiContext::new(self.token_program.to_account_info(), cpi_accounts),
+            amount,
+        )
     }
 
-    pub fn deposit(&mut self, amount: u64) -> Result<()> {
+    pub fn deposit(&mut self, amount: u64, bumps: &DepositBumps) -> Result<()> {
+        let owner_key = self.owner.key();
         let signer_seeds: [&[&[u8]];1] = [&[
             b"auth".as_ref(),
-            &[PROTOCOL_AUTHORITY_BUMP]
+            owner_key.as_ref(),
+            &[bumps.protocol_authority]
         ]];
 
-        let accounts = vec![
-            AccountMeta::new(self.protocol_authority.key(), true),                      // owner   
-            AccountMeta::new(self.obligation.key(), false),                             // obligation
-            AccountMeta::new_readonly(self.lending_market.key(), false),                // lending_market
-            AccountMeta::new_readonly(self.lending_market_authority.key(), false),      // lending_market_authority
-            AccountMeta::new(self.reserve_collateral.key(), false),                     // reserve
-            AccountMeta::new_readonly(self.reserve_liquidity_mint.key(), false),        // reserve_liquidity_mint
-            AccountMeta::new(self.reserve_liquidity_supply.key(), false),               // reserve_liquidity_supply
-            AccountMeta::new(self.reserve_collateral_mint.key(), false),                // reserve_collateral_mint
-            AccountMeta::new(self.reserve_destination_deposit_collateral.key(), false), // reserve_destination_deposit_collateral
-            AccountMeta::new(self.user_source_liquidity.key(), false),                  // user_source_liquidity
-            AccountMeta::new_readonly(self.kamino_lending_program.key(), false),        // [optional] placeholder_user_destination_collateral
-            AccountMeta::new_readonly(self.token_program.key(), false),                 // collateral_token_program
-            AccountMeta::new_readonly(self.token_program.key(), false),                 // liquidity_token_program
-            AccountMeta::new_readonly(self.instruction_sysvar_account.key(), false),    // instruction_sysvar_account
-            AccountMeta::new(self.obligation_farm_state.key(), false),         // obligation_farm_user_state
-            AccountMeta::new(self.reserve_farm_state.key(), false),            // reserve_farm_state
-            AccountMeta::new_readonly(self.farms_program.key(), false),                 // farms_program
-        ];
-
-        let account_infos = vec![
-            self.protocol_authority.to_account_info(),
-            self.obligation.to_account_info(),
-            self.lending_market.to_account_info(),
-            self.lending_market_authority.to_account_info(),
-            self.reserve_collateral.to_account_info(),
-            self.reserve_liquidity_mint.to_account_info(),
-            self.reserve_liquidity_supply.to_account_info(),
-            self.reserve_collateral_mint.to_account_info(),
-            self.reserve_destination_deposit_collateral.to_account_info(),
-            self.user_source_liquidity.to_account_info(),
-            self.kamino_lending_program.to_account_info(),
-            self.token_program.to_account_info(),
-            self.token_program.to_account_info(),
-            self.instruction_sysvar_account.to_account_info(),
-            self.obligation_farm_state.to_account_info(),
-            self.reserve_farm_state.to_account_info(),
-            self.farms_program.to_account_info(),
-        ];
-
-        let deposit_ix = Instruction {
-            program_id: self.kamino_lending_program.key(),
-            accounts,
-            data: vec![
-                DEPOSIT_RESERVE_LIQUIDITY_AND_OBLIGATION_COLLATERAL_V2_DISCRIMINATOR.as_ref(),
-                &amount.to_le_bytes(),
-            ].concat(),
-        };
-
-        invoke_signed(
-            &deposit_ix,
-            &account_infos,
+        deposit_reserve_liquidity_and_obligation_collateral_v2(
+            DepositReserveLiquidityAndObligationCollateralV2 {
+                owner: self.protocol_authority.to_account_info(),
+                obligation: self.obligation.to_account_info(),
+                lending_market: self.lending_market.to_account_info(),
+                lending_market_authority: self.lending_market_authority.to_account_info(),
+                reserve: self.reserve_collateral.to_account_info(),
+                reserve_liquidity_mint: self.reserve_liquidity_mint.to_account_info(),
+                reserve_liquidity_supply: self.reserve_liquidity_supply.to_account_info(),
+                reserve_collateral_mint: self.reserve_collateral_mint.to_account_info(),
+                reserve_destination_deposit_collateral: self.reserve_destination_deposit_collateral.to_account_info(),
+                user_source_liquidity: self.vault.to_account_info(),
+                token_program: self.token_program.to_account_info(),
+                instruction_sysvar_account: self.instruction_sysvar_account.to_account_info(),
+                obligation_farm_user_state: Some(self.obligation_farm_state.to_account_info()),
+                reserve_farm_state: Some(self.reserve_farm_state.to_account_info()),
+                farms_program: self.farms_program.to_account_info(),
+                kamino_lending_program: self.kamino_lending_program.to_account_info(),
+            },
+            amount,
             &signer_seeds,
         )?;
 
+        self.position.deposited = self.position.deposited.checked_add(amount).ok_or(error!(LoopingError::MathOverflow))?;
+
         Ok(())
     }
 }
\ No newline at end of file
@@ -0,0 +1,178 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{accessor, transfer, Token, Transfer};
+use crate::{constant::KAMINO_FARMS_PROGRAM_ID, error::LoopingError, kamino::{harvest_reward, FarmState, HarvestReward, RewardInfo}, state::{Config, Position}};
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct Harvest<'info> {
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"auth", owner.key().as_ref()],
+        bump,
+    )]
+    pub protocol_authority: SystemAccount<'info>,
+    #[account(
+        seeds = [
+            b"position",
+            owner.key().as_ref(),
+            position.lending_market.as_ref(),
+        ],
+        bump = position.bump,
+        has_one = owner,
+    )]
+    pub position: Account<'info, Position>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused.withdraw @ LoopingError::Paused,
+    )]
+    pub config: Account<'info, Config>,
+
+    pub token_program: Program<'info, Token>,
+
+    /// Kamino Farms-specific accounts
+    #[account(
+        mut,
+        seeds = [
+            b"user",
+            reserve_farm_state.key().as_ref(),
+            position.obligation.as_ref(),
+        ],
+        bump,
+        seeds::program = KAMINO_FARMS_PROGRAM_ID,
+    )]
+    /// CHECK: checked by the Kamino Farms program
+    pub obligation_farm_state: UncheckedAccount<'info>,
+    #[account(mut)]
+    /// CHECK: checked by the Kamino Farms program
+    pub reserve_farm_state: UncheckedAccount<'info>,
+    /// CHECK: checked by the Kamino Farms program
+    pub global_config: UncheckedAccount<'info>,
+    /// CHECK: checked by the Kamino Farms program
+    pub farm_vaults_authority: UncheckedAccount<'info>,
+    /// CHECK: checked by the Kamino Farms program
+    pub scope_prices: Option<UncheckedAccount<'info>>,
+    #[account(address = KAMINO_FARMS_PROGRAM_ID)]
+    /// CHECK: checked by the Kamino Farms program
+    pub farms_program: UncheckedAccount<'info>,
+}
+
+/// Accounts each reward index of the farm needs, passed in as remaining accounts in reward index order:
+/// reward mint, reward vault, user destination reward, farm rewards vault and farm rewards treasury vault.
+pub const REWARD_ACCOUNTS_LEN: usize = 5;
+
+/// Accounts of one reward index of the farm
+pub struct RewardAccounts<'info> {
+    pub reward_index: u64,
+    pub reward_mint: AccountInfo<'info>,
+    /// Token account of the protocol authority the farm pays into
+    pub reward_vault: AccountInfo<'info>,
+    pub user_destination_reward: AccountInfo<'info>,
+    pub rewards_vault: AccountInfo<'info>,
+    pub rewards_treasury_vault: AccountInfo<'info>,
+}
+
+impl<'info> Harvest<'info> {
+    /// # Match the remaining accounts to the farm rewards
+    /// 
+    /// Every initialized reward of the farm gets its `REWARD_ACCOUNTS_LEN` accounts. The vault and the
+    /// destination have to be token accounts of the reward mint held by the protocol authority and
+    /// the owner, the rest of the farm accounts are checked by the Kamino Farms program.
+    pub fn reward_accounts(&self, remaining_accounts: &[AccountInfo<'info>]) -> Result<Vec<RewardAccounts<'info>>> {
+        let rewards: Vec<RewardInfo> = FarmState::load(&self.reserve_farm_state)?.rewards().collect();
+        require_gte!(remaining_accounts.len(), rewards.len() * REWARD_ACCOUNTS_LEN, LoopingError::InsufficientRemainingAccounts);
+
+        rewards
+            .iter()
+            .zip(remaining_accounts.chunks(REWARD_ACCOUNTS_LEN))
+            .enumerate()
+            .map(|(reward_index, (reward, accounts))| {
+                require_keys_eq!(accounts[0].key(), reward.reward_mint, ErrorCode::ConstraintAddress);
+                require_keys_eq!(accounts[3].key(), reward.rewards_vault, ErrorCode::ConstraintAddress);
+                check_token_account(&accounts[1], &reward.reward_mint, &self.protocol_authority.key())?;
+                check_token_account(&accounts[2], &reward.reward_mint, &self.owner.key())?;
+
+                Ok(RewardAccounts {
+                    reward_index: reward_index as u64,
+                    reward_mint: accounts[0].clone(),
+                    reward_vault: accounts[1].clone(),
+                    user_destination_reward: accounts[2].clone(),
+                    rewards_vault: accounts[3].clone(),
+                    rewards_treasury_vault: accounts[4].clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// # Harvest a reward of the farm
+    /// 
+    /// The farm is the collateral farm of the collateral reserve, the only one `initialize` enrolls
+    /// the obligation in. The user state seeds tie it to the obligation of the position.
+    /// 
+    /// Returns the amount harvested into the reward vault.
+    pub fn harvest(&self, reward: &RewardAccounts<'info>, bumps: &HarvestBumps) -> Result<u64> {
+        let owner_key = self.owner.key();
+        let signer_seeds: [&[&[u8]];1] = [&[
+            b"auth".as_ref(),
+            owner_key.as_ref(),
+            &[bumps.protocol_authority]
+        ]];
+
+        let balance_before = accessor::amount(&reward.reward_vault)?;
+
+        harvest_reward(
+            HarvestReward {
+                owner: self.protocol_authority.to_account_info(),
+                user_state: self.obligation_farm_state.to_account_info(),
+                farm_state: self.reserve_farm_state.to_account_info(),
+                global_config: self.global_config.to_account_info(),
+                reward_mint: reward.reward_mint.clone(),
+                user_reward_ata: reward.reward_vault.clone(),
+                rewards_vault: reward.rewards_vault.clone(),
+                rewards_treasury_vault: reward.rewards_treasury_vault.clone(),
+                farm_vaults_authority: self.farm_vaults_authority.to_account_info(),
+                scope_prices: self.scope_prices.as_ref().map(|scope_prices| scope_prices.to_account_info()),
+                token_program: self.token_program.to_account_info(),
+                farms_program: self.farms_program.to_account_info(),
+            },
+            reward.reward_index,
+            &signer_seeds,
+        )?;
+
+        accessor::amount(&reward.reward_vault)?.checked_sub(balance_before).ok_or(error!(LoopingError::MathOverflow))
+    }
+
+    /// # Send the harvested rewards to the owner
+    /// 
+    /// Only the amount harvested in this instruction is transferred, anything else sitting in the
+    /// vault stays there.
+    pub fn transfer_to_owner(&self, reward: &RewardAccounts<'info>, amount: u64, bumps: &HarvestBumps) -> Result<()> {
+        let owner_key = self.owner.key();
+        let signer_seeds: [&[&[u8]];1] = [&[
+            b"auth".as_ref(),
+            owner_key.as_ref(),
+            &[bumps.protocol_authority]
+        ]];
+
+        let cpi_accounts = Transfer {
+            from: reward.reward_vault.clone(),
+            to: reward.user_destination_reward.clone(),
+            authority: self.protocol_authority.to_account_info(),
+        };
+
+        transfer(
+            CpiContext::new_with_signer(self.token_program.to_account_info(), cpi_accounts, &signer_seeds),
+            amount,
+        )
+    }
+}
+
+/// A token account of `mint` held by `authority`.
+fn check_token_account(token_account: &AccountInfo, mint: &Pubkey, authority: &Pubkey) -> Result<()> {
+    require_keys_eq!(*token_account.owner, Token::id(), ErrorCode::AccountOwnedByWrongProgram);
+    require_keys_eq!(accessor::mint(token_account)?, *mint, ErrorCode::ConstraintTokenMint);
+    require_keys_eq!(accessor::authority(token_account)?, *authority, ErrorCode::ConstraintTokenOwner);
+
+    Ok(())
+}
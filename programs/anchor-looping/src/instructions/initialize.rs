@@ -1,21 +1,37 @@
-use anchor_lang::{prelude::*, solana_program::{instruction::Instruction, program::{invoke, invoke_signed}}};
-use anchor_spl::token::Token;
-use crate::constant::{KAMINO_PROGRAM_ID, PROTOCOL_AUTHORITY_BUMP};
-
-const INIT_USER_METADATA_DISCRIMINATOR: [u8; 8] = [117, 169, 176, 69, 197, 23, 15, 162];
-const INIT_OBLIGATION_DISCRIMINATOR: [u8; 8] = [251, 10, 231, 76, 27, 11, 159, 96];
-const INIT_OBLIGATION_FARMS_FOR_RESERVE_DISCRIMINATOR: [u8; 8] = [136, 63, 15, 186, 211, 152, 168, 164];
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Mint, Token};
+use crate::{constant::KAMINO_PROGRAM_ID, error::LoopingError, kamino::{check_reserve, init_obligation, init_obligation_farms_for_reserve, init_user_metadata, InitObligation, InitObligationFarmsForReserve, InitUserMetadata}, state::{MarketConfig, Position}};
 
 #[derive(Accounts)]
 pub struct Initialize<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
+    pub owner: Signer<'info>,
     #[account(
         mut,
-        seeds = [b"auth"],
-        bump = PROTOCOL_AUTHORITY_BUMP,
+        seeds = [b"auth", owner.key().as_ref()],
+        bump,
     )]
     pub protocol_authority: SystemAccount<'info>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + Position::INIT_SPACE,
+        seeds = [
+            b"position",
+            owner.key().as_ref(),
+            lending_market.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub position: Account<'info, Position>,
+    #[account(
+        seeds = [b"market_config", lending_market.key().as_ref()],
+        bump = market_config.bump,
+    )]
+    pub market_config: Box<Account<'info, MarketConfig>>,
+    pub collateral_mint: Account<'info, Mint>,
+    pub debt_mint: Account<'info, Mint>,
 
     #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
     /// CHECK: checked by the Kamino program
@@ -36,6 +52,21 @@ pub struct Initialize<'info> {
     )]
     /// CHECK: checked by the Kamino program
     pub user_metadata: UncheckedAccount<'info>,
+    #[account(
+        seeds = [b"referrer"],
+        bump,
+    )]
+    pub referrer: SystemAccount<'info>,
+    #[account(
+        seeds = [
+            b"user_meta", 
+            referrer.key().as_ref()                     // Owner of the user metadata
+        ],
+        bump,
+        seeds::program = KAMINO_PROGRAM_ID,
+    )]
+    /// CHECK: checked by the Kamino program
+    pub referrer_user_metadata: UncheckedAccount<'info>,
     #[account(
         mut,
         seeds = [
@@ -66,6 +97,8 @@ pub struct Initialize<'info> {
     #[account(mut)]
     /// CHECK: checked by the Kamino program
     pub reserve: UncheckedAccount<'info>,
+    /// CHECK: checked by the Kamino program
+    pub reserve_borrow: UncheckedAccount<'info>,
     #[account(
         mut,
         seeds = [
@@ -89,53 +122,70 @@ pub struct Initialize<'info> {
 }
 
 impl<'info> Initialize<'info> {
+    /// # Check the reserves against the market config
+    /// 
+    /// The collateral and borrow reserves have to be a listed pair, with the mints the pair lists.
+    pub fn check_reserve_pair(&self) -> Result<()> {
+        let reserve_pair = self.market_config.reserve_pair(&self.reserve.key(), &self.reserve_borrow.key())?;
+        require_keys_eq!(self.collateral_mint.key(), reserve_pair.collateral_mint, LoopingError::MarketNotAllowed);
+        require_keys_eq!(self.debt_mint.key(), reserve_pair.debt_mint, LoopingError::MarketNotAllowed);
+
+        check_reserve(&self.reserve, &self.lending_market.key(), &reserve_pair.collateral_mint)?;
+        check_reserve(&self.reserve_borrow, &self.lending_market.key(), &reserve_pair.debt_mint)
+    }
+
+    /// # Set up the position account
+    /// 
+    /// Records which market, reserves and mints the owner is looping so that every other
+    /// instruction can be checked against it.
+    pub fn initialize_position(&mut self, bumps: &InitializeBumps) -> Result<()> {
+        self.position.set_inner(Position {
+            owner: self.owner.key(),
+            delegate: Pubkey::default(),
+            lending_market: self.lending_market.key(),
+            obligation: self.obligation.key(),
+            reserve_collateral: self.reserve.key(),
+            reserve_borrow: self.reserve_borrow.key(),
+            collateral_mint: self.collateral_mint.key(),
+            debt_mint: self.debt_mint.key(),
+            deposited: 0,
+            withdrawn: 0,
+            borrowed: 0,
+            repaid: 0,
+            authority_bump: bumps.protocol_authority,
+            bump: bumps.position,
+        });
+
+        Ok(())
+    }
+
     /// # Set up the user metadata account
     /// 
     /// This is a step needed for all wallets that use the Kamino program. 
     /// 
-    /// In this occasion we don't need to pass in any referrer but if you let user 
-    /// interact with Kamino through your program you probably want to pass in one 
-    /// of your PDAs as referrer to cash in some rewards.
-    pub fn initialize_user_metadata(&mut self) -> Result<()> {
+    /// Our referrer PDA is registered as referrer, so Kamino shares the borrow fees of the
+    /// obligation with it. Its user metadata has to be set up first with `initialize_referrer`.
+    pub fn initialize_user_metadata(&mut self, bumps: &InitializeBumps) -> Result<()> {
+        let owner_key = self.owner.key();
         let signer_seeds: [&[&[u8]];1] = [&[
             b"auth".as_ref(),
-            &[PROTOCOL_AUTHORITY_BUMP]
+            owner_key.as_ref(),
+            &[bumps.protocol_authority]
         ]];
 
-        let accounts = vec![
-            AccountMeta::new_readonly(self.protocol_authority.key(), true),         // owner
-            AccountMeta::new(self.payer.key(), true),                               // fee_payer
-            AccountMeta::new(self.user_metadata.key(), false),                      // user metadata account that we are going to initialize
-            AccountMeta::new_readonly(self.kamino_lending_program.key(), false),    // [optional] referrer 
-            AccountMeta::new_readonly(self.rent.key(), false),                      // rent
-            AccountMeta::new_readonly(self.system_program.key(), false),            // system program
-        ];
-
-        let account_infos = vec![
-            self.protocol_authority.to_account_info(),
-            self.payer.to_account_info(),
-            self.user_metadata.to_account_info(),
-            self.kamino_lending_program.to_account_info(),
-            self.rent.to_account_info(),
-            self.system_program.to_account_info(),
-        ];
-
-        let initialize_user_metadata_ix = Instruction {
-            program_id: self.kamino_lending_program.key(),
-            accounts,
-            data: vec![
-                INIT_USER_METADATA_DISCRIMINATOR.as_ref(),
-                Pubkey::default().as_ref(),                                         // Lookup Table (used in the frontend)
-            ].concat(),
-        };
-        
-        invoke_signed(
-            &initialize_user_metadata_ix, 
-            &account_infos, 
-            &signer_seeds
-        )?;
-
-        Ok(())
+        init_user_metadata(
+            InitUserMetadata {
+                owner: self.protocol_authority.to_account_info(),
+                fee_payer: self.payer.to_account_info(),
+                user_metadata: self.user_metadata.to_account_info(),
+                referrer_user_metadata: Some(self.referrer_user_metadata.to_account_info()),
+                rent: self.rent.to_account_info(),
+                system_program: self.system_program.to_account_info(),
+                kamino_lending_program: self.kamino_lending_program.to_account_info(),
+            },
+            Pubkey::default(),                                                      // Lookup Table (used in the frontend)
+            &signer_seeds,
+        )
     }
 
     /// # Set up the obligation account
@@ -145,99 +195,53 @@ impl<'info> Initialize<'info> {
     /// 
     /// Some of the inputs are used in their frontend and for this reason we pass in
     /// Pubkey::default().as_ref(), or 0u8 as some of the parameters. 
-    pub fn initialize_obligation(&mut self) -> Result<()> {
+    pub fn initialize_obligation(&mut self, bumps: &InitializeBumps) -> Result<()> {
+        let owner_key = self.owner.key();
         let signer_seeds: [&[&[u8]];1] = [&[
             b"auth".as_ref(),
-            &[PROTOCOL_AUTHORITY_BUMP]
+            owner_key.as_ref(),
+            &[bumps.protocol_authority]
         ]];
 
-        let accounts = vec![
-            AccountMeta::new_readonly(self.protocol_authority.key(), true),         // obbligation_owner
-            AccountMeta::new(self.payer.key(), true),                               // fee_payer
-            AccountMeta::new(self.obligation.key(), false),                         // obligation account that we are going to initialize
-            AccountMeta::new_readonly(self.lending_market.key(), false),            // lending_market
-            AccountMeta::new_readonly(self.system_program.key(), false),            // seed1_account (used in the frontend)
-            AccountMeta::new_readonly(self.system_program.key(), false),            // seed2_account (used in the frontend)
-            AccountMeta::new_readonly(self.user_metadata.key(), false),             // owner_user_metadata
-            AccountMeta::new_readonly(self.rent.key(), false),                      // rent
-            AccountMeta::new_readonly(self.system_program.key(), false),            // system_program
-        ];
-
-        let account_infos = vec![
-            self.protocol_authority.to_account_info(),
-            self.payer.to_account_info(),
-            self.obligation.to_account_info(),
-            self.lending_market.to_account_info(),
-            self.user_metadata.to_account_info(),
-            self.rent.to_account_info(),
-            self.system_program.to_account_info(),
-        ];
-
-        let initialize_obligation_ix = Instruction {
-            program_id: self.kamino_lending_program.key(),
-            accounts,
-            data: vec![
-                INIT_OBLIGATION_DISCRIMINATOR.as_ref(),
-                &[0],                                                               // Tag (used in the frontend)
-                &[0],                                                               // Id (used in the frontend)
-            ].concat(),
-        };
-
-        invoke_signed(
-            &initialize_obligation_ix, 
-            &account_infos, 
-            &signer_seeds
-        )?;
-
-        Ok(())
+        init_obligation(
+            InitObligation {
+                obligation_owner: self.protocol_authority.to_account_info(),
+                fee_payer: self.payer.to_account_info(),
+                obligation: self.obligation.to_account_info(),
+                lending_market: self.lending_market.to_account_info(),
+                seed1_account: self.system_program.to_account_info(),              // Seed1 account (used in the frontend)
+                seed2_account: self.system_program.to_account_info(),              // Seed2 account (used in the frontend)
+                owner_user_metadata: self.user_metadata.to_account_info(),
+                rent: self.rent.to_account_info(),
+                system_program: self.system_program.to_account_info(),
+                kamino_lending_program: self.kamino_lending_program.to_account_info(),
+            },
+            0,                                                                      // Tag (used in the frontend)
+            0,                                                                      // Id (used in the frontend)
+            &signer_seeds,
+        )
     }
 
     /// # Set up the obligation farms for the reserve
     /// 
     /// This is an account that is needed only if there is a farm on the reserve.
     pub fn initialize_obligation_farms_for_reserve(&mut self) -> Result<()> {
-        let accounts = vec![
-            AccountMeta::new(self.payer.key(), true),                              // payer
-            AccountMeta::new_readonly(self.protocol_authority.key(), false),       // owner
-            AccountMeta::new(self.obligation.key(), false),                        // obligation
-            AccountMeta::new_readonly(self.lending_market_authority.key(), false), // lending_market_authority
-            AccountMeta::new(self.reserve.key(), false),                           // reserve
-            AccountMeta::new(self.reserve_farm_state.key(), false),                // reserve_farm_state
-            AccountMeta::new(self.obligation_farm_state.key(), false),             // obligation_farm account that we are going to initialize
-            AccountMeta::new_readonly(self.lending_market.key(), false),           // lending_market
-            AccountMeta::new_readonly(self.farms_program.key(), false),            // farms_program
-            AccountMeta::new_readonly(self.rent.key(), false),                     // rent
-            AccountMeta::new_readonly(self.system_program.key(), false),           // system_program
-        ];
-
-        let account_infos = vec![
-            self.payer.to_account_info(),
-            self.protocol_authority.to_account_info(),
-            self.obligation.to_account_info(),
-            self.lending_market_authority.to_account_info(),
-            self.reserve.to_account_info(),
-            self.reserve_farm_state.to_account_info(),
-            self.obligation_farm_state.to_account_info(),
-            self.lending_market.to_account_info(),
-            self.farms_program.to_account_info(),
-            self.rent.to_account_info(),
-            self.system_program.to_account_info(),
-        ];
-
-        let initialize_obligation_farms_for_reserve_ix = Instruction {
-            program_id: self.kamino_lending_program.key(),
-            accounts,
-            data: vec![
-                INIT_OBLIGATION_FARMS_FOR_RESERVE_DISCRIMINATOR.as_ref(),
-                &[0],                                                               // Mode (used in the frontend)
-            ].concat(),
-        };
-
-        invoke(
-            &initialize_obligation_farms_for_reserve_ix, 
-            &account_infos, 
-        )?;
-
-        Ok(())
+        init_obligation_farms_for_reserve(
+            InitObligationFarmsForReserve {
+                payer: self.payer.to_account_info(),
+                owner: self.protocol_authority.to_account_info(),
+                obligation: self.obligation.to_account_info(),
+                lending_market_authority: self.lending_market_authority.to_account_info(),
+                reserve: self.reserve.to_account_info(),
+                reserve_farm_state: self.reserve_farm_state.to_account_info(),
+                obligation_farm: self.obligation_farm_state.to_account_info(),
+                lending_market: self.lending_market.to_account_info(),
+                farms_program: self.farms_program.to_account_info(),
+                rent: self.rent.to_account_info(),
+                system_program: self.system_program.to_account_info(),
+                kamino_lending_program: self.kamino_lending_program.to_account_info(),
+            },
+            0,                                                                      // Mode (used in the frontend)
+        )
     }
 }
\ No newline at end of file
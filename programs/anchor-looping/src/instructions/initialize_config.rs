@@ -0,0 +1,43 @@
+use anchor_lang::prelude::*;
+use crate::{program::AnchorLooping, state::{Config, PauseFlags}};
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Config::INIT_SPACE,
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, AnchorLooping>,
+    #[account(constraint = program_data.upgrade_authority_address == Some(authority.key()))]
+    pub program_data: Account<'info, ProgramData>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> InitializeConfig<'info> {
+    /// # Initialize the config
+    /// 
+    /// Only the upgrade authority of the program can create it, everything starts unpaused
+    /// and without fees. Reward swaps can't slip until the admin sets a bound.
+    pub fn initialize_config(&mut self, admin: Pubkey, bumps: &InitializeConfigBumps) -> Result<()> {
+        self.config.set_inner(Config {
+            admin,
+            pending_admin: Pubkey::default(),
+            paused: PauseFlags::default(),
+            borrow_fee_bps: 0,
+            repay_fee_bps: 0,
+            max_platform_fee_bps: 0,
+            compound_tip_bps: 0,
+            max_reward_slippage_bps: 0,
+            bump: bumps.config,
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,45 @@
+use anchor_lang::prelude::*;
+use crate::{error::LoopingError, state::{Config, MarketConfig}};
+
+#[derive(Accounts)]
+pub struct InitializeMarketConfig<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ LoopingError::NotAdmin,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + MarketConfig::INIT_SPACE,
+        seeds = [b"market_config", lending_market.key().as_ref()],
+        bump,
+    )]
+    pub market_config: Account<'info, MarketConfig>,
+    /// CHECK: checked by the Kamino program when the market is used
+    pub lending_market: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> InitializeMarketConfig<'info> {
+    /// # Initialize the market config
+    /// 
+    /// Only the config admin can list a lending market, its reserve pairs are listed afterwards with
+    /// `set_reserve_pair`.
+    pub fn initialize_market_config(&mut self, max_price_deviation_bps: u16, max_ltv_bps: u16, bumps: &InitializeMarketConfigBumps) -> Result<()> {
+        MarketConfig::check_limits(max_price_deviation_bps, max_ltv_bps)?;
+
+        self.market_config.set_inner(MarketConfig {
+            lending_market: self.lending_market.key(),
+            max_price_deviation_bps,
+            max_ltv_bps,
+            reserve_pairs: Vec::new(),
+            bump: bumps.market_config,
+        });
+
+        Ok(())
+    }
+}
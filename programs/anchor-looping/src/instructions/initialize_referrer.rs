@@ -0,0 +1,97 @@
+use anchor_lang::prelude::*;
+use crate::{constant::KAMINO_PROGRAM_ID, error::LoopingError, kamino::{init_referrer_token_state, init_user_metadata, InitReferrerTokenState, InitUserMetadata}, state::Config};
+
+#[derive(Accounts)]
+pub struct InitializeReferrer<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ LoopingError::NotAdmin,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        seeds = [b"referrer"],
+        bump,
+    )]
+    pub referrer: SystemAccount<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    pub system_program: Program<'info, System>,
+
+    /// Kamino-specific accounts
+    #[account(
+        mut,
+        seeds = [
+            b"user_meta", 
+            referrer.key().as_ref()                     // Owner of the user metadata
+        ],
+        bump,
+        seeds::program = KAMINO_PROGRAM_ID,
+    )]
+    /// CHECK: checked by the Kamino program
+    pub referrer_user_metadata: UncheckedAccount<'info>,
+    /// CHECK: checked by the Kamino program
+    pub lending_market: UncheckedAccount<'info>,
+    /// CHECK: checked by the Kamino program
+    pub reserve: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"referrer_acc",
+            referrer.key().as_ref(),
+            reserve.key().as_ref(),
+        ],
+        bump,
+        seeds::program = KAMINO_PROGRAM_ID,
+    )]
+    /// CHECK: checked by the Kamino program
+    pub referrer_token_state: UncheckedAccount<'info>,
+    #[account(address = KAMINO_PROGRAM_ID)]
+    /// CHECK: checked by the Kamino program
+    pub kamino_lending_program: AccountInfo<'info>,
+}
+
+impl<'info> InitializeReferrer<'info> {
+    /// # Set up the referrer user metadata
+    /// 
+    /// The referrer is a PDA of ours with its own Kamino user metadata, every user metadata created
+    /// by `initialize` points at it.
+    pub fn initialize_user_metadata(&mut self, bumps: &InitializeReferrerBumps) -> Result<()> {
+        let signer_seeds: [&[&[u8]];1] = [&[
+            b"referrer".as_ref(),
+            &[bumps.referrer]
+        ]];
+
+        init_user_metadata(
+            InitUserMetadata {
+                owner: self.referrer.to_account_info(),
+                fee_payer: self.admin.to_account_info(),
+                user_metadata: self.referrer_user_metadata.to_account_info(),
+                referrer_user_metadata: None,
+                rent: self.rent.to_account_info(),
+                system_program: self.system_program.to_account_info(),
+                kamino_lending_program: self.kamino_lending_program.to_account_info(),
+            },
+            Pubkey::default(),                                                      // Lookup Table (used in the frontend)
+            &signer_seeds,
+        )
+    }
+
+    /// # Set up the referrer token state of the reserve
+    /// 
+    /// Kamino accrues the referral fees of the reserve in it, a borrow by a referred obligation
+    /// needs the one of its borrow reserve.
+    pub fn initialize_referrer_token_state(&mut self) -> Result<()> {
+        init_referrer_token_state(InitReferrerTokenState {
+            payer: self.admin.to_account_info(),
+            lending_market: self.lending_market.to_account_info(),
+            reserve: self.reserve.to_account_info(),
+            referrer: self.referrer.to_account_info(),
+            referrer_token_state: self.referrer_token_state.to_account_info(),
+            rent: self.rent.to_account_info(),
+            system_program: self.system_program.to_account_info(),
+            kamino_lending_program: self.kamino_lending_program.to_account_info(),
+        })
+    }
+}
@@ -8,4 +8,49 @@ pub mod looping;
 pub use looping::*;
 
 pub mod repay;
-pub use repay::*;
\ No newline at end of file
+pub use repay::*;
+
+pub mod withdraw;
+pub use withdraw::*;
+
+pub mod close_position;
+pub use close_position::*;
+
+pub mod set_delegate;
+pub use set_delegate::*;
+
+pub mod leverage;
+pub use leverage::*;
+
+pub mod deleverage;
+pub use deleverage::*;
+
+pub mod initialize_market_config;
+pub use initialize_market_config::*;
+
+pub mod update_market_config;
+pub use update_market_config::*;
+
+pub mod initialize_config;
+pub use initialize_config::*;
+
+pub mod update_config;
+pub use update_config::*;
+
+pub mod accept_admin;
+pub use accept_admin::*;
+
+pub mod withdraw_fees;
+pub use withdraw_fees::*;
+
+pub mod initialize_referrer;
+pub use initialize_referrer::*;
+
+pub mod withdraw_referrer_fees;
+pub use withdraw_referrer_fees::*;
+
+pub mod harvest;
+pub use harvest::*;
+
+pub mod compound;
+pub use compound::*;
@@ -1,24 +1,50 @@
-use anchor_lang::{prelude::*, solana_program::{instruction::Instruction, program::{invoke, invoke_signed}}};
-use anchor_spl::token::{Mint, Token, TokenAccount};
-use crate::constant::{JUPITER_PROGRAM_ID, KAMINO_PROGRAM_ID, PROTOCOL_AUTHORITY_BUMP};
-
-const REFRESH_RESERVE_DISCRIMINATOR: [u8; 8] = [2, 218, 138, 235, 79, 201, 25, 102];
-const REFRESH_OBLIGATION_DISCRIMINATOR: [u8; 8] = [33, 132, 147, 228, 151, 192, 72, 89];
-const WITHDRAW_OBLIGATION_COLLATERAL_AND_REEDEM_RESERVE_COLLATERAL_V2_DISCRIMINATOR: [u8; 8] = [235, 52, 119, 152, 149, 197, 20, 7];
-const REPAY_OBLIGATION_LIQUIDITY_V2_DISCRIMINATOR: [u8; 8] = [116, 174, 213, 76, 180, 53, 210, 144];
-const SHARED_ACCOUNTS_EXACT_OUT_ROUTE_DISCRIMINATOR: [u8; 8] = [176, 209, 105, 168, 154, 125, 69, 62];
-const EXACT_OUT_ROUTE_DISCRIMINATOR: [u8; 8] = [208, 51, 239, 151, 123, 43, 237, 92];
+use anchor_lang::prelude::*;
+use anchor_spl::token::{transfer, Mint, Token, TokenAccount, Transfer};
+use crate::{constant::{JUPITER_PROGRAM_ID, KAMINO_PROGRAM_ID}, error::LoopingError, jupiter::{check_swap_data, swap_route, SwapAccounts, SwapResult}, kamino::{check_swap_price, deposit_reserve_liquidity_and_obligation_collateral_v2, refresh_position, repay_obligation_liquidity_v2, withdraw_obligation_collateral_and_redeem_reserve_collateral_v2, DepositReserveLiquidityAndObligationCollateralV2, RefreshPosition, RepayObligationLiquidityV2, WithdrawObligationCollateralAndRedeemReserveCollateralV2}, state::{Config, MarketConfig, Position}};
 
+#[event_cpi]
 #[derive(Accounts)]
 pub struct Repay<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
+    pub authority: Signer<'info>,
+    pub owner: SystemAccount<'info>,
     #[account(
         mut,
-        seeds = [b"auth"],
+        seeds = [b"auth", owner.key().as_ref()],
         bump,
     )]
     pub protocol_authority: SystemAccount<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"position",
+            owner.key().as_ref(),
+            lending_market.key().as_ref(),
+        ],
+        bump = position.bump,
+        has_one = owner,
+        constraint = position.is_owner_or_delegate(authority.key) @ LoopingError::Unauthorized,
+        has_one = lending_market,
+        has_one = obligation,
+        has_one = reserve_collateral,
+        has_one = reserve_borrow,
+        constraint = input_mint.key() == position.collateral_mint,
+        constraint = output_mint.key() == position.debt_mint,
+    )]
+    pub position: Box<Account<'info, Position>>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused.repay @ LoopingError::Paused,
+    )]
+    pub config: Box<Account<'info, Config>>,
+    #[account(
+        seeds = [b"market_config", lending_market.key().as_ref()],
+        bump = market_config.bump,
+        constraint = market_config.reserve_pair(&position.reserve_collateral, &position.reserve_borrow).is_ok() @ LoopingError::MarketNotAllowed,
+    )]
+    pub market_config: Box<Account<'info, MarketConfig>>,
     pub input_mint: Box<Account<'info, Mint>>,
     #[account(
         mut,
@@ -34,6 +60,22 @@ pub struct Repay<'info> {
     )]
     pub output_vault: Box<Account<'info, TokenAccount>>,
 
+    #[account(
+        seeds = [b"treasury"],
+        bump,
+    )]
+    pub treasury: SystemAccount<'info>,
+    #[account(
+        mut,
+        associated_token::mint = output_mint,
+        associated_token::authority = treasury,
+    )]
+    pub treasury_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        token::authority = treasury,
+    )]
+    pub platform_fee_vault: Option<Box<Account<'info, TokenAccount>>>,
     #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
     /// CHECK: checked by the Kamino program
     pub instruction_sysvar_account: UncheckedAccount<'info>,
@@ -121,7 +163,7 @@ pub struct Repay<'info> {
 
     /// Jupiter-specific accounts
     /// CHECK: checked by the Jupiter program
-    pub event_authority: AccountInfo<'info>,
+    pub jupiter_event_authority: AccountInfo<'info>,
     #[account(address = JUPITER_PROGRAM_ID)]
     /// CHECK: checked by the Jupiter program
     pub jupiter_program: AccountInfo<'info>,
@@ -129,345 +171,206 @@ pub struct Repay<'info> {
 }
 
 impl<'info> Repay<'info> {
-    /// # Refresh the reserve collateral
-    /// 
-    /// This is a step needed to refresh the reserve collateral before interacting with it.
+    /// # Refresh the position
     /// 
-    /// We are going to only use scope as oracle (this is Kamino in-house oracle that doesn't require any crank
-    /// since they do it for you)
-    pub fn refresh_reserve_collateral(&mut self) -> Result<()> {
-        let accounts = vec![
-            AccountMeta::new(self.reserve_collateral.key(), false),                 // reserve
-            AccountMeta::new_readonly(self.lending_market.key(), false),            // lending_market
-            AccountMeta::new_readonly(self.kamino_lending_program.key(), false),    // [optional] pyth_oracle
-            AccountMeta::new_readonly(self.kamino_lending_program.key(), false),    // [optional] switchboard_price_oracle
-            AccountMeta::new_readonly(self.kamino_lending_program.key(), false),    // [optional] switchboard_twap_oracle
-            AccountMeta::new_readonly(self.scope_oracle.key(), false),              // [optional] scope_oracle
-        ];
-        let account_infos = vec![
-            self.reserve_collateral.to_account_info(),
-            self.lending_market.to_account_info(),
-            self.kamino_lending_program.to_account_info(),
-            self.scope_oracle.to_account_info(),
-        ];
+    /// Both reserves, any other reserve the obligation uses and then the obligation, see `refresh_position`.
+    pub fn refresh_position(&self, reserves: &[AccountInfo<'info>]) -> Result<()> {
+        refresh_position(RefreshPosition {
+            obligation: self.obligation.to_account_info(),
+            lending_market: self.lending_market.to_account_info(),
+            reserve_collateral: self.reserve_collateral.to_account_info(),
+            reserve_borrow: Some(self.reserve_borrow.to_account_info()),
+            scope_oracle: self.scope_oracle.to_account_info(),
+            kamino_lending_program: self.kamino_lending_program.to_account_info(),
+            reserves,
+        })
+    }
 
-        let refresh_reserve_collateral_ix = Instruction {
-            program_id: self.kamino_lending_program.key(),
-            accounts,
-            data: vec![
-                REFRESH_RESERVE_DISCRIMINATOR.as_ref(),
-            ].concat(),
-        };
+    pub fn withdraw_collateral(&mut self, amount: u64, bumps: &RepayBumps) -> Result<u64> {
+        let owner_key = self.owner.key();
+        let signer_seeds: [&[&[u8]];1] = [&[
+            b"auth".as_ref(),
+            owner_key.as_ref(),
+            &[bumps.protocol_authority]
+        ]];
 
-        invoke(
-            &refresh_reserve_collateral_ix,
-            &account_infos,
+        let balance_before = self.input_vault.amount;
+
+        withdraw_obligation_collateral_and_redeem_reserve_collateral_v2(
+            WithdrawObligationCollateralAndRedeemReserveCollateralV2 {
+                owner: self.protocol_authority.to_account_info(),
+                obligation: self.obligation.to_account_info(),
+                lending_market: self.lending_market.to_account_info(),
+                lending_market_authority: self.lending_market_authority.to_account_info(),
+                withdraw_reserve: self.reserve_collateral.to_account_info(),
+                reserve_liquidity_mint: self.input_mint.to_account_info(),
+                reserve_source_collateral: self.reserve_source_collateral.to_account_info(),
+                reserve_collateral_mint: self.reserve_collateral_mint.to_account_info(),
+                reserve_liquidity_supply: self.reserve_liquidity_supply.to_account_info(),
+                user_destination_liquidity: self.input_vault.to_account_info(),
+                token_program: self.token_program.to_account_info(),
+                instruction_sysvar_account: self.instruction_sysvar_account.to_account_info(),
+                obligation_farm_user_state: Some(self.obligation_farm_state.to_account_info()),
+                reserve_farm_state: Some(self.reserve_farm_state.to_account_info()),
+                farms_program: self.farms_program.to_account_info(),
+                kamino_lending_program: self.kamino_lending_program.to_account_info(),
+            },
+            amount,
+            &signer_seeds,
         )?;
 
-        Ok(())
+        self.input_vault.reload()?;
+        let withdrawn = self.input_vault.amount.checked_sub(balance_before).ok_or(error!(LoopingError::MathOverflow))?;
+        self.position.withdrawn = self.position.withdrawn.checked_add(withdrawn).ok_or(error!(LoopingError::MathOverflow))?;
+
+        Ok(withdrawn)
     }
 
-    /// # Refresh the borrow collateral
-    /// 
-    /// This is a step needed to refresh the borrow collateral before interacting with it.
-    /// 
-    /// We are going to only use scope as oracle (this is Kamino in-house oracle that doesn't require any crank
-    /// since they do it for you)
-    pub fn refresh_reserve_borrow(&mut self) -> Result<()> {
-        let accounts = vec![
-            AccountMeta::new(self.reserve_borrow.key(), false),                          // reserve
-            AccountMeta::new_readonly(self.lending_market.key(), false),            // lending_market
-            AccountMeta::new_readonly(self.kamino_lending_program.key(), false),    // [optional] pyth_oracle
-            AccountMeta::new_readonly(self.kamino_lending_program.key(), false),    // [optional] switchboard_price_oracle
-            AccountMeta::new_readonly(self.kamino_lending_program.key(), false),    // [optional] switchboard_twap_oracle
-            AccountMeta::new_readonly(self.scope_oracle.key(), false),              // [optional] scope_oracle
-        ];
-        let account_infos = vec![
-            self.reserve_borrow.to_account_info(),
-            self.lending_market.to_account_info(),
-            self.kamino_lending_program.to_account_info(),
-            self.scope_oracle.to_account_info(),
-        ];
+    pub fn swap_for_collateral(&mut self, swap_data: &[u8], amount: u64, slippage_bps: u16, remaining_accounts: &[AccountInfo<'info>], bumps: &RepayBumps) -> Result<SwapResult> {
+        // Perform a discriminator, amount and slippage check
+        let reserve_pair = self.market_config.reserve_pair(&self.position.reserve_collateral, &self.position.reserve_borrow)?;
+        let route = check_swap_data(swap_data, amount, slippage_bps, reserve_pair.max_slippage_bps, self.config.max_platform_fee_bps)?;
 
-        let refresh_reserve_collateral_ix = Instruction {
-            program_id: self.kamino_lending_program.key(),
-            accounts,
-            data: vec![
-                REFRESH_RESERVE_DISCRIMINATOR.as_ref(),
-            ].concat(),
+        // Jupiter skims the platform fee into the treasury, in the output mint of exact-in routes and the input mint of exact-out ones
+        let platform_fee_account = match route.platform_fee_bps {
+            0 => None,
+            _ => Some(self.platform_fee_vault.as_ref().ok_or(error!(LoopingError::MissingPlatformFeeAccount))?.to_account_info()),
         };
 
-        invoke(
-            &refresh_reserve_collateral_ix,
-            &account_infos,
-        )?;
-
-        Ok(())
-    }
-
-    /// # Refresh the obligation
-    /// 
-    /// This is a step needed to refresh the obligation before interacting with it.
-    /// 
-    /// Note: We need to supply as remaining account any cranked reserve account that is used in the obligation for
-    /// both collateral and borrows.
-    pub fn refresh_obligation(&mut self) -> Result<()> {
-        let mut accounts = vec![
-            AccountMeta::new_readonly(self.lending_market.key(), false),            // lending_market
-            AccountMeta::new(self.obligation.key(), false),                         // obligation
-        ];
-        accounts.push(AccountMeta::new(self.reserve_collateral.key(), false));
-        accounts.push(AccountMeta::new(self.reserve_borrow.key(), false));
-
-        let mut account_infos = vec![
-            self.lending_market.to_account_info(),
-            self.obligation.to_account_info(),
-        ];
-        account_infos.push(self.reserve_collateral.to_account_info());
-        account_infos.push(self.reserve_borrow.to_account_info());
+        let owner_key = self.owner.key();
+        let signer_seeds: [&[&[u8]];1] = [&[
+            b"auth".as_ref(),
+            owner_key.as_ref(),
+            &[bumps.protocol_authority]
+        ]];
 
-        let refresh_obligation_ix = Instruction {
-            program_id: self.kamino_lending_program.key(),
-            accounts,
-            data: vec![
-                REFRESH_OBLIGATION_DISCRIMINATOR.as_ref(),
-            ].concat(),
+        let accounts = SwapAccounts {
+            token_program: self.token_program.to_account_info(),
+            user_transfer_authority: self.protocol_authority.to_account_info(),
+            source_token_account: self.input_vault.to_account_info(),
+            destination_token_account: self.output_vault.to_account_info(),
+            source_mint: self.input_mint.to_account_info(),
+            destination_mint: self.output_mint.to_account_info(),
+            event_authority: self.jupiter_event_authority.to_account_info(),
+            jupiter_program: self.jupiter_program.to_account_info(),
+            platform_fee_account,
         };
 
-        invoke(
-            &refresh_obligation_ix,
-            &account_infos,
-        )?;
+        let swap = swap_route(accounts, &route, swap_data, remaining_accounts, &signer_seeds)?;
+        check_swap_price(&self.reserve_collateral, &self.reserve_borrow, swap.spent, swap.received, self.market_config.max_price_deviation_bps)?;
 
-        Ok(())
+        Ok(swap)
     }
 
-    pub fn withdraw_collateral(&mut self, amount: u64) -> Result<()> {
+    /// # Put the unswapped collateral back
+    /// 
+    /// The exact-out route rarely spends all the collateral we withdrew for it. What it left in the vault
+    /// goes back into the obligation instead of sitting there.
+    pub fn redeposit(&mut self, amount: u64, bumps: &RepayBumps) -> Result<()> {
+        if amount == 0 {
+            return Ok(());
+        }
+
+        let owner_key = self.owner.key();
         let signer_seeds: [&[&[u8]];1] = [&[
             b"auth".as_ref(),
-            &[PROTOCOL_AUTHORITY_BUMP]
+            owner_key.as_ref(),
+            &[bumps.protocol_authority]
         ]];
 
-        let accounts = vec![
-            AccountMeta::new(self.protocol_authority.key(), true),                      // owner
-            AccountMeta::new(self.obligation.key(), false),                             // obligation
-            AccountMeta::new_readonly(self.lending_market.key(), false),                // lending_market
-            AccountMeta::new_readonly(self.lending_market_authority.key(), false),      // lending_market_authority
-            AccountMeta::new(self.reserve_collateral.key(), false),                     // withdraw_reserve
-            AccountMeta::new_readonly(self.input_mint.key(), false),                    // reserve_liquidity_mint
-            AccountMeta::new(self.reserve_source_collateral.key(), false),              // reserve_source_collateral
-            AccountMeta::new(self.reserve_collateral_mint.key(), false),                // reserve_collateral_mint
-            AccountMeta::new(self.reserve_liquidity_supply.key(), false),               // reserve_liquidity_supply
-            AccountMeta::new(self.input_vault.key(), false),                            // user_destination_liquidity
-            AccountMeta::new_readonly(self.kamino_lending_program.key(), false),        // [optional] placeholder_user_destination_collateral
-            AccountMeta::new_readonly(self.token_program.key(), false),                 // collateral_token_program
-            AccountMeta::new_readonly(self.token_program.key(), false),                 // liquidity_token_program
-            AccountMeta::new_readonly(self.instruction_sysvar_account.key(), false),    // instruction_sysvar_account
-            AccountMeta::new(self.obligation_farm_state.key(), false),                  // [optional] obligation_farm_user_state
-            AccountMeta::new(self.reserve_farm_state.key(), false),                     // [optional] reserve_farm_state
-            AccountMeta::new_readonly(self.farms_program.key(), false),                 // farms_program
-        ];
-
-        let account_infos = vec![
-            self.protocol_authority.to_account_info(),
-            self.obligation.to_account_info(),
-            self.lending_market.to_account_info(),
-            self.lending_market_authority.to_account_info(),
-            self.reserve_collateral.to_account_info(),
-            self.input_mint.to_account_info(),
-            self.reserve_source_collateral.to_account_info(),
-            self.reserve_collateral_mint.to_account_info(),
-            self.reserve_liquidity_supply.to_account_info(),
-            self.input_vault.to_account_info(),
-            self.kamino_lending_program.to_account_info(),
-            self.token_program.to_account_info(),
-            self.instruction_sysvar_account.to_account_info(),
-            self.obligation_farm_state.to_account_info(),
-            self.reserve_farm_state.to_account_info(),
-            self.farms_program.to_account_info(),
-        ];
-
-        let borrow_from_collateral_ix = Instruction {
-            program_id: self.kamino_lending_program.key(),
-            accounts,
-            data: vec![
-                WITHDRAW_OBLIGATION_COLLATERAL_AND_REEDEM_RESERVE_COLLATERAL_V2_DISCRIMINATOR.as_ref(),
-                &amount.to_le_bytes(),
-            ].concat(),
-        };
-
-        invoke_signed(
-            &borrow_from_collateral_ix,
-            &account_infos,
+        deposit_reserve_liquidity_and_obligation_collateral_v2(
+            DepositReserveLiquidityAndObligationCollateralV2 {
+                owner: self.protocol_authority.to_account_info(),
+                obligation: self.obligation.to_account_info(),
+                lending_market: self.lending_market.to_account_info(),
+                lending_market_authority: self.lending_market_authority.to_account_info(),
+                reserve: self.reserve_collateral.to_account_info(),
+                reserve_liquidity_mint: self.input_mint.to_account_info(),
+                reserve_liquidity_supply: self.reserve_liquidity_supply.to_account_info(),
+                reserve_collateral_mint: self.reserve_collateral_mint.to_account_info(),
+                reserve_destination_deposit_collateral: self.reserve_source_collateral.to_account_info(),
+                user_source_liquidity: self.input_vault.to_account_info(),
+                token_program: self.token_program.to_account_info(),
+                instruction_sysvar_account: self.instruction_sysvar_account.to_account_info(),
+                obligation_farm_user_state: Some(self.obligation_farm_state.to_account_info()),
+                reserve_farm_state: Some(self.reserve_farm_state.to_account_info()),
+                farms_program: self.farms_program.to_account_info(),
+                kamino_lending_program: self.kamino_lending_program.to_account_info(),
+            },
+            amount,
             &signer_seeds,
         )?;
 
+        self.position.deposited = self.position.deposited.checked_add(amount).ok_or(error!(LoopingError::MathOverflow))?;
+
         Ok(())
     }
 
-    pub fn swap_for_collateral(&mut self, swap_data: &Vec<u8>, amount: u64, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
-        // Perform a discriminator, amount and slippage check
-        let swap_data_length = swap_data.len();
-        let bps_offset = swap_data_length - size_of::<u16>() - size_of::<u8>();
-        let amount_offset = bps_offset - size_of::<u64>() - size_of::<u64>();
-
-        require_eq!(amount, u64::from_le_bytes(swap_data[amount_offset..amount_offset + size_of::<u64>()].try_into().unwrap()));
-        require_eq!(50, u16::from_le_bytes(swap_data[bps_offset..bps_offset + size_of::<u16>()].try_into().unwrap()));
-
+    /// # Repay the debt
+    /// 
+    /// At most `amount`, what the swap delivered less room for the repay fee, is repaid: anything else
+    /// sitting in the vault stays there.
+    pub fn repay_debt(&mut self, amount: u64, bumps: &RepayBumps) -> Result<u64> {
+        let owner_key = self.owner.key();
         let signer_seeds: [&[&[u8]];1] = [&[
             b"auth".as_ref(),
-            &[PROTOCOL_AUTHORITY_BUMP]
+            owner_key.as_ref(),
+            &[bumps.protocol_authority]
         ]];
 
-        let (account_infos, accounts) = match swap_data {            
-            data if data.starts_with(&EXACT_OUT_ROUTE_DISCRIMINATOR) => {
-                let mut account_infos = vec![
-                    self.token_program.to_account_info(),
-                    self.protocol_authority.to_account_info(),
-                    self.input_vault.to_account_info(),
-                    self.output_vault.to_account_info(),
-                    self.input_mint.to_account_info(),
-                    self.output_mint.to_account_info(),
-                    self.event_authority.to_account_info(),
-                    self.jupiter_program.to_account_info(),
-                ];
-                account_infos.extend(remaining_accounts.iter().map(|acc| AccountInfo { ..acc.clone() }));
-
-                let mut accounts = vec![
-                    AccountMeta::new_readonly(self.token_program.key(), false),         // token program
-                    AccountMeta::new_readonly(self.protocol_authority.key(), true),     // user transfer authority
-                    AccountMeta::new(self.input_vault.key(), false),                    // user source token account
-                    AccountMeta::new(self.output_vault.key(), false),                   // user destination token account
-                    AccountMeta::new_readonly(self.jupiter_program.key(), false),       // [optional] destination token account
-                    AccountMeta::new_readonly(self.input_mint.key(), false),            // source mint
-                    AccountMeta::new_readonly(self.output_mint.key(), false),           // destination mint
-                    AccountMeta::new_readonly(self.jupiter_program.key(), false),       // [optional] platform fee account
-                    AccountMeta::new_readonly(self.jupiter_program.key(), false),       // [optional] token 2022 program
-                    AccountMeta::new_readonly(self.event_authority.key(), false),       // event authority
-                    AccountMeta::new_readonly(self.jupiter_program.key(), false),       // jupiter program
-                ];
-                accounts.extend(remaining_accounts.iter().map(|acc| {
-                    AccountMeta {
-                        pubkey: *acc.key,
-                        is_signer: false,
-                        is_writable: acc.is_writable,
-                    }
-                }));
-
-                (account_infos, accounts)
-            }
-            data if data.starts_with(&SHARED_ACCOUNTS_EXACT_OUT_ROUTE_DISCRIMINATOR) => {
-                // Build the swap instruction accounts
-                let mut account_infos = vec![
-                    self.token_program.to_account_info(),
-                    remaining_accounts[0].to_account_info(),
-                    self.protocol_authority.to_account_info(),
-                    self.input_vault.to_account_info(),
-                    remaining_accounts[1].to_account_info(),
-                    remaining_accounts[2].to_account_info(),
-                    self.output_vault.to_account_info(),
-                    self.input_mint.to_account_info(),
-                    self.output_mint.to_account_info(),
-                    self.event_authority.to_account_info(),
-                    self.jupiter_program.to_account_info(),
-                ];
-                account_infos.extend(remaining_accounts.iter().map(|acc| AccountInfo { ..acc.clone() }));
-
-                let mut accounts = vec![
-                    AccountMeta::new_readonly(self.token_program.key(), false),         // token program
-                    AccountMeta::new_readonly(remaining_accounts[0].key(), false),      // program authority
-                    AccountMeta::new_readonly(self.protocol_authority.key(), true),     // user transfer authority
-                    AccountMeta::new(self.input_vault.key(), false),                    // source token account
-                    AccountMeta::new(remaining_accounts[1].key(), false),               // program source token account
-                    AccountMeta::new(remaining_accounts[2].key(), false),               // program destination token account
-                    AccountMeta::new(self.output_vault.key(), false),                   // destination token account
-                    AccountMeta::new_readonly(self.input_mint.key(), false),            // source mint
-                    AccountMeta::new_readonly(self.output_mint.key(), false),           // destination mint
-                    AccountMeta::new_readonly(self.jupiter_program.key(), false),       // [optional] platform fee account
-                    AccountMeta::new_readonly(self.jupiter_program.key(), false),       // [optional] token 2022 program
-                    AccountMeta::new_readonly(self.event_authority.key(), false),       // event authority
-                    AccountMeta::new_readonly(self.jupiter_program.key(), false),       // jupiter program
-                ];
-                accounts.extend(remaining_accounts.iter().skip(3).map(|acc| {
-                    AccountMeta {
-                        pubkey: *acc.key,
-                        is_signer: false,
-                        is_writable: acc.is_writable,
-                    }
-                }));
-            
-                (account_infos, accounts)
-            }
-            _ => {
-                return Err(Error::from(ProgramError::InvalidInstructionData));
-            }
-        };
-
-        let swap_ix = Instruction {
-            program_id: self.jupiter_program.key(),
-            accounts,
-            data: swap_data.to_vec(),
-        };
+        self.output_vault.reload()?;
+        let balance_before = self.output_vault.amount;
+
+        repay_obligation_liquidity_v2(
+            RepayObligationLiquidityV2 {
+                owner: self.protocol_authority.to_account_info(),
+                obligation: self.obligation.to_account_info(),
+                lending_market: self.lending_market.to_account_info(),
+                repay_reserve: self.reserve_borrow.to_account_info(),
+                reserve_liquidity_mint: self.output_mint.to_account_info(),
+                reserve_destination_liquidity: self.borrow_reserve_destination_liquidity.to_account_info(),
+                user_source_liquidity: self.output_vault.to_account_info(),
+                token_program: self.token_program.to_account_info(),
+                instruction_sysvar_account: self.instruction_sysvar_account.to_account_info(),
+                obligation_farm_user_state: None,
+                reserve_farm_state: None,
+                lending_market_authority: self.lending_market_authority.to_account_info(),
+                farms_program: self.farms_program.to_account_info(),
+                kamino_lending_program: self.kamino_lending_program.to_account_info(),
+            },
+            amount,
+            &signer_seeds,
+        )?;
 
-        invoke_signed(&swap_ix, &account_infos, &signer_seeds)?;
+        self.output_vault.reload()?;
+        let repaid = balance_before.checked_sub(self.output_vault.amount).ok_or(error!(LoopingError::MathOverflow))?;
+        self.position.repaid = self.position.repaid.checked_add(repaid).ok_or(error!(LoopingError::MathOverflow))?;
 
-        Ok(())
+        Ok(repaid)
     }
 
-    pub fn repay_debt(&mut self) -> Result<()> {
+    /// # Pay the repay fee to the treasury
+    pub fn pay_fee(&mut self, fee: u64, bumps: &RepayBumps) -> Result<()> {
+        if fee == 0 {
+            return Ok(());
+        }
+
+        let owner_key = self.owner.key();
         let signer_seeds: [&[&[u8]];1] = [&[
             b"auth".as_ref(),
-            &[PROTOCOL_AUTHORITY_BUMP]
+            owner_key.as_ref(),
+            &[bumps.protocol_authority]
         ]];
 
-        let accounts = vec![
-            AccountMeta::new(self.protocol_authority.key(), true),                      // owner   
-            AccountMeta::new(self.obligation.key(), false),                             // obligation
-            AccountMeta::new_readonly(self.lending_market.key(), false),                // lending_market
-            AccountMeta::new(self.reserve_borrow.key(), false),                         // repay_reserve
-            AccountMeta::new_readonly(self.output_mint.key(), false),                   // reserve_liquidity_mint
-            AccountMeta::new(self.borrow_reserve_destination_liquidity.key(), false),   // reserve_destination_liquidity
-            AccountMeta::new(self.output_vault.key(), false),                           // user_source_liquidity
-            AccountMeta::new_readonly(self.token_program.key(), false),                 // token_program
-            AccountMeta::new_readonly(self.instruction_sysvar_account.key(), false),    // instruction_sysvar_account
-            AccountMeta::new_readonly(self.kamino_lending_program.key(), false),        // [optional] obligation_farm_user_state
-            AccountMeta::new_readonly(self.kamino_lending_program.key(), false),        // [optional] reserve_farm_state
-            AccountMeta::new_readonly(self.lending_market_authority.key(), false),      // lending_market_authority
-            AccountMeta::new_readonly(self.farms_program.key(), false),                 // farms_program
-        ];
-
-        let account_infos = vec![
-            self.protocol_authority.to_account_info(),
-            self.obligation.to_account_info(),
-            self.lending_market.to_account_info(),
-            self.reserve_borrow.to_account_info(),
-            self.output_mint.to_account_info(),
-            self.borrow_reserve_destination_liquidity.to_account_info(),
-            self.output_vault.to_account_info(),
-            self.kamino_lending_program.to_account_info(),
-            self.token_program.to_account_info(),
-            self.instruction_sysvar_account.to_account_info(),
-            self.lending_market_authority.to_account_info(),
-            self.farms_program.to_account_info(),
-        ];
-
-        self.output_vault.reload()?;
-
-        let deposit_ix = Instruction {
-            program_id: self.kamino_lending_program.key(),
-            accounts,
-            data: vec![
-                REPAY_OBLIGATION_LIQUIDITY_V2_DISCRIMINATOR.as_ref(),
-                &u64::MAX.to_le_bytes(),
-            ].concat(),
+        let cpi_accounts = Transfer {
+            from: self.output_vault.to_account_info(),
+            to: self.treasury_vault.to_account_info(),
+            authority: self.protocol_authority.to_account_info(),
         };
 
-        invoke_signed(
-            &deposit_ix,
-            &account_infos,
-            &signer_seeds,
-        )?;
-
-        Ok(())
+        transfer(
+            CpiContext::new_with_signer(self.token_program.to_account_info(), cpi_accounts, &signer_seeds),
+            fee,
+        )
     }
-}
\ No newline at end of file
+}
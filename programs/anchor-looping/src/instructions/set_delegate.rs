@@ -0,0 +1,30 @@
+use anchor_lang::prelude::*;
+use crate::state::Position;
+
+#[derive(Accounts)]
+pub struct SetDelegate<'info> {
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"position",
+            owner.key().as_ref(),
+            position.lending_market.as_ref(),
+        ],
+        bump = position.bump,
+        has_one = owner,
+    )]
+    pub position: Account<'info, Position>,
+}
+
+impl<'info> SetDelegate<'info> {
+    /// # Set the delegate of the position
+    /// 
+    /// The delegate can call `looping` and `repay` on behalf of the owner (e.g. a bot that keeps
+    /// the position at a target leverage). Passing `None` revokes it.
+    pub fn set_delegate(&mut self, delegate: Option<Pubkey>) -> Result<()> {
+        self.position.delegate = delegate.unwrap_or_default();
+
+        Ok(())
+    }
+}
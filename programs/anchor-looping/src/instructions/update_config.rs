@@ -0,0 +1,47 @@
+use anchor_lang::prelude::*;
+use crate::{error::LoopingError, state::{Config, PauseFlags, MAX_BPS, MAX_FEE_BPS}};
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    pub admin: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ LoopingError::NotAdmin,
+    )]
+    pub config: Account<'info, Config>,
+}
+
+impl<'info> UpdateConfig<'info> {
+    /// # Update the config
+    /// 
+    /// `pending_admin` starts a handoff that the new admin completes with `accept_admin`, passing
+    /// `None` cancels it.
+    pub fn update_config(&mut self, pending_admin: Option<Pubkey>, paused: PauseFlags, borrow_fee_bps: u16, repay_fee_bps: u16, max_platform_fee_bps: u8, compound_tip_bps: u16) -> Result<()> {
+        require_gte!(MAX_FEE_BPS, borrow_fee_bps, LoopingError::FeeTooHigh);
+        require_gte!(MAX_FEE_BPS, repay_fee_bps, LoopingError::FeeTooHigh);
+        require_gte!(MAX_FEE_BPS, max_platform_fee_bps as u16, LoopingError::FeeTooHigh);
+        require_gte!(MAX_FEE_BPS, compound_tip_bps, LoopingError::FeeTooHigh);
+
+        self.config.pending_admin = pending_admin.unwrap_or_default();
+        self.config.paused = paused;
+        self.config.borrow_fee_bps = borrow_fee_bps;
+        self.config.repay_fee_bps = repay_fee_bps;
+        self.config.max_platform_fee_bps = max_platform_fee_bps;
+        self.config.compound_tip_bps = compound_tip_bps;
+
+        Ok(())
+    }
+
+    /// # Set the reward swap slippage bound
+    /// 
+    /// Bounds the slippage keepers can request on the `compound` reward swaps.
+    pub fn set_max_reward_slippage(&mut self, max_reward_slippage_bps: u16) -> Result<()> {
+        require_gte!(MAX_BPS, max_reward_slippage_bps, LoopingError::SlippageTooHigh);
+
+        self.config.max_reward_slippage_bps = max_reward_slippage_bps;
+
+        Ok(())
+    }
+}
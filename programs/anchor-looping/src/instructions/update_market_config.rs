@@ -0,0 +1,85 @@
+use anchor_lang::prelude::*;
+use crate::{error::LoopingError, kamino::check_reserve, state::{Config, MarketConfig, ReservePair, MAX_RESERVE_PAIRS}};
+
+#[derive(Accounts)]
+pub struct UpdateMarketConfig<'info> {
+    pub admin: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ LoopingError::NotAdmin,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [b"market_config", market_config.lending_market.as_ref()],
+        bump = market_config.bump,
+    )]
+    pub market_config: Account<'info, MarketConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SetReservePair<'info> {
+    pub admin: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ LoopingError::NotAdmin,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [b"market_config", market_config.lending_market.as_ref()],
+        bump = market_config.bump,
+    )]
+    pub market_config: Account<'info, MarketConfig>,
+    /// CHECK: checked against the pair with `check_reserve`
+    pub reserve_collateral: UncheckedAccount<'info>,
+    /// CHECK: checked against the pair with `check_reserve`
+    pub reserve_borrow: UncheckedAccount<'info>,
+}
+
+impl<'info> UpdateMarketConfig<'info> {
+    /// # Update the market config
+    pub fn update_market_config(&mut self, max_price_deviation_bps: u16, max_ltv_bps: u16) -> Result<()> {
+        MarketConfig::check_limits(max_price_deviation_bps, max_ltv_bps)?;
+
+        self.market_config.max_price_deviation_bps = max_price_deviation_bps;
+        self.market_config.max_ltv_bps = max_ltv_bps;
+
+        Ok(())
+    }
+
+    /// # Delist a reserve pair
+    pub fn remove_reserve_pair(&mut self, reserve_collateral: Pubkey, reserve_borrow: Pubkey) -> Result<()> {
+        self.market_config.reserve_pair(&reserve_collateral, &reserve_borrow)?;
+        self.market_config.reserve_pairs.retain(|pair| pair.reserve_collateral != reserve_collateral || pair.reserve_borrow != reserve_borrow);
+
+        Ok(())
+    }
+}
+
+impl<'info> SetReservePair<'info> {
+    /// # List a reserve pair
+    /// 
+    /// Replaces the limits of the pair if it is already listed. The reserves have to belong to the
+    /// market and lend the mints the pair lists.
+    pub fn set_reserve_pair(&mut self, reserve_pair: ReservePair) -> Result<()> {
+        reserve_pair.check_limits()?;
+        require_keys_eq!(self.reserve_collateral.key(), reserve_pair.reserve_collateral, LoopingError::InvalidReservePair);
+        require_keys_eq!(self.reserve_borrow.key(), reserve_pair.reserve_borrow, LoopingError::InvalidReservePair);
+        check_reserve(&self.reserve_collateral, &self.market_config.lending_market, &reserve_pair.collateral_mint)?;
+        check_reserve(&self.reserve_borrow, &self.market_config.lending_market, &reserve_pair.debt_mint)?;
+
+        let reserve_pairs = &mut self.market_config.reserve_pairs;
+        match reserve_pairs.iter_mut().find(|pair| pair.reserve_collateral == reserve_pair.reserve_collateral && pair.reserve_borrow == reserve_pair.reserve_borrow) {
+            Some(pair) => *pair = reserve_pair,
+            None => {
+                require_gt!(MAX_RESERVE_PAIRS, reserve_pairs.len(), LoopingError::ReservePairsFull);
+                reserve_pairs.push(reserve_pair);
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,218 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{transfer, Mint, Token, TokenAccount, Transfer};
+use crate::{constant::KAMINO_PROGRAM_ID, error::LoopingError, kamino::{refresh_position, withdraw_obligation_collateral_and_redeem_reserve_collateral_v2, RefreshPosition, WithdrawObligationCollateralAndRedeemReserveCollateralV2}, state::{Config, MarketConfig, Position}};
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct Withdraw<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"auth", owner.key().as_ref()],
+        bump,
+    )]
+    pub protocol_authority: SystemAccount<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"position",
+            owner.key().as_ref(),
+            lending_market.key().as_ref(),
+        ],
+        bump = position.bump,
+        has_one = owner,
+        has_one = lending_market,
+        has_one = obligation,
+        has_one = reserve_collateral,
+        constraint = reserve_liquidity_mint.key() == position.collateral_mint,
+    )]
+    pub position: Account<'info, Position>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused.withdraw @ LoopingError::Paused,
+    )]
+    pub config: Box<Account<'info, Config>>,
+    #[account(
+        seeds = [b"market_config", lending_market.key().as_ref()],
+        bump = market_config.bump,
+        constraint = market_config.reserve_pair(&position.reserve_collateral, &position.reserve_borrow).is_ok() @ LoopingError::MarketNotAllowed,
+    )]
+    pub market_config: Box<Account<'info, MarketConfig>>,
+    pub reserve_liquidity_mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = reserve_liquidity_mint,
+        associated_token::authority = protocol_authority,
+    )]
+    pub vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = reserve_liquidity_mint,
+        token::authority = owner,
+    )]
+    pub user_destination_liquidity: Account<'info, TokenAccount>,
+
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: checked by the Kamino program
+    pub instruction_sysvar_account: UncheckedAccount<'info>,
+    pub token_program: Program<'info, Token>,
+
+    /// Kamino-specific accounts
+    #[account(
+        mut,
+        seeds = [
+            &[0],                                       // Tag
+            &[0],                                       // Id
+            protocol_authority.key().as_ref(),          // Obligation owner
+            lending_market.key().as_ref(),              // Lending market
+            Pubkey::default().as_ref(),                 // Seed1 account
+            Pubkey::default().as_ref(),                 // Seed2 account
+        ],
+        bump,
+        seeds::program = KAMINO_PROGRAM_ID,
+    )]
+    /// CHECK: checked by the Kamino program
+    pub obligation: UncheckedAccount<'info>,
+    /// CHECK: checked by the Kamino program
+    pub lending_market: UncheckedAccount<'info>,
+    #[account(
+        seeds = [
+            b"lma",
+            lending_market.key().as_ref()
+        ],
+        bump,
+        seeds::program = KAMINO_PROGRAM_ID,
+    )]
+    /// CHECK: checked by the Kamino program
+    pub lending_market_authority: UncheckedAccount<'info>,
+    #[account(mut)]
+    /// CHECK: checked by the Kamino program
+    pub reserve_collateral: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        address = position.reserve_borrow,
+    )]
+    /// CHECK: checked by the Kamino program
+    pub reserve_borrow: Option<UncheckedAccount<'info>>,
+    #[account(mut)]
+    /// CHECK: checked by the Kamino program
+    pub reserve_liquidity_supply: UncheckedAccount<'info>,
+    /// CHECK: checked by the Kamino program
+    #[account(mut)]
+    pub reserve_collateral_mint: UncheckedAccount<'info>,
+    #[account(mut)]
+    /// CHECK: checked by the Kamino program
+    pub reserve_source_collateral: UncheckedAccount<'info>,
+    /// CHECK: checked by the Kamino program
+    pub scope_oracle: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"user",
+            reserve_farm_state.key().as_ref(),
+            obligation.key().as_ref(),
+        ],
+        bump,
+        seeds::program = farms_program.key(),
+    )]
+    /// CHECK: checked by the Kamino program
+    pub obligation_farm_state: UncheckedAccount<'info>,
+    #[account(mut)]
+    /// CHECK: checked by the Kamino program
+    pub reserve_farm_state: UncheckedAccount<'info>,
+    #[account(address = KAMINO_PROGRAM_ID)]
+    /// CHECK: checked by the Kamino program
+    pub kamino_lending_program: AccountInfo<'info>,
+    /// CHECK: checked by the Kamino program
+    pub farms_program: UncheckedAccount<'info>,
+}
+
+impl<'info> Withdraw<'info> {
+    /// # Refresh the position
+    /// 
+    /// Both reserves, any other reserve the obligation uses and then the obligation, see `refresh_position`.
+    pub fn refresh_position(&self, reserves: &[AccountInfo<'info>]) -> Result<()> {
+        refresh_position(RefreshPosition {
+            obligation: self.obligation.to_account_info(),
+            lending_market: self.lending_market.to_account_info(),
+            reserve_collateral: self.reserve_collateral.to_account_info(),
+            reserve_borrow: self.reserve_borrow.as_ref().map(|reserve_borrow| reserve_borrow.to_account_info()),
+            scope_oracle: self.scope_oracle.to_account_info(),
+            kamino_lending_program: self.kamino_lending_program.to_account_info(),
+            reserves,
+        })
+    }
+
+    /// # Withdraw the collateral from the obligation
+    /// 
+    /// The amount is expressed in reserve collateral tokens, passing `u64::MAX` withdraws everything
+    /// that Kamino lets us withdraw without breaking the LTV of the obligation.
+    /// 
+    /// Returns the amount of liquidity redeemed into the vault.
+    pub fn withdraw_collateral(&mut self, amount: u64, bumps: &WithdrawBumps) -> Result<u64> {
+        let owner_key = self.owner.key();
+        let signer_seeds: [&[&[u8]];1] = [&[
+            b"auth".as_ref(),
+            owner_key.as_ref(),
+            &[bumps.protocol_authority]
+        ]];
+
+        let balance_before = self.vault.amount;
+
+        withdraw_obligation_collateral_and_redeem_reserve_collateral_v2(
+            WithdrawObligationCollateralAndRedeemReserveCollateralV2 {
+                owner: self.protocol_authority.to_account_info(),
+                obligation: self.obligation.to_account_info(),
+                lending_market: self.lending_market.to_account_info(),
+                lending_market_authority: self.lending_market_authority.to_account_info(),
+                withdraw_reserve: self.reserve_collateral.to_account_info(),
+                reserve_liquidity_mint: self.reserve_liquidity_mint.to_account_info(),
+                reserve_source_collateral: self.reserve_source_collateral.to_account_info(),
+                reserve_collateral_mint: self.reserve_collateral_mint.to_account_info(),
+                reserve_liquidity_supply: self.reserve_liquidity_supply.to_account_info(),
+                user_destination_liquidity: self.vault.to_account_info(),
+                token_program: self.token_program.to_account_info(),
+                instruction_sysvar_account: self.instruction_sysvar_account.to_account_info(),
+                obligation_farm_user_state: Some(self.obligation_farm_state.to_account_info()),
+                reserve_farm_state: Some(self.reserve_farm_state.to_account_info()),
+                farms_program: self.farms_program.to_account_info(),
+                kamino_lending_program: self.kamino_lending_program.to_account_info(),
+            },
+            amount,
+            &signer_seeds,
+        )?;
+
+        self.vault.reload()?;
+        let withdrawn = self.vault.amount.checked_sub(balance_before).ok_or(error!(LoopingError::MathOverflow))?;
+        self.position.withdrawn = self.position.withdrawn.checked_add(withdrawn).ok_or(error!(LoopingError::MathOverflow))?;
+
+        Ok(withdrawn)
+    }
+
+    /// # Send the withdrawn liquidity to the owner
+    /// 
+    /// Only the amount redeemed in this instruction is transferred, anything else sitting in the
+    /// vault stays there.
+    pub fn transfer_to_owner(&mut self, amount: u64, bumps: &WithdrawBumps) -> Result<()> {
+        let owner_key = self.owner.key();
+        let signer_seeds: [&[&[u8]];1] = [&[
+            b"auth".as_ref(),
+            owner_key.as_ref(),
+            &[bumps.protocol_authority]
+        ]];
+
+        let cpi_accounts = Transfer {
+            from: self.vault.to_account_info(),
+            to: self.user_destination_liquidity.to_account_info(),
+            authority: self.protocol_authority.to_account_info(),
+        };
+
+        transfer(
+            CpiContext::new_with_signer(self.token_program.to_account_info(), cpi_accounts, &signer_seeds),
+            amount,
+        )
+    }
+}
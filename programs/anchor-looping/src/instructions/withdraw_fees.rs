@@ -0,0 +1,55 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{transfer, Mint, Token, TokenAccount, Transfer};
+use crate::{error::LoopingError, state::Config};
+
+#[derive(Accounts)]
+pub struct WithdrawFees<'info> {
+    pub admin: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ LoopingError::NotAdmin,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        seeds = [b"treasury"],
+        bump,
+    )]
+    pub treasury: SystemAccount<'info>,
+    pub mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = treasury,
+    )]
+    pub treasury_vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = mint,
+    )]
+    pub destination: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+impl<'info> WithdrawFees<'info> {
+    /// # Withdraw the collected fees
+    /// 
+    /// Passing `u64::MAX` empties the treasury vault of the mint.
+    pub fn withdraw_fees(&mut self, amount: u64, bumps: &WithdrawFeesBumps) -> Result<()> {
+        let signer_seeds: [&[&[u8]];1] = [&[
+            b"treasury".as_ref(),
+            &[bumps.treasury]
+        ]];
+
+        let cpi_accounts = Transfer {
+            from: self.treasury_vault.to_account_info(),
+            to: self.destination.to_account_info(),
+            authority: self.treasury.to_account_info(),
+        };
+
+        transfer(
+            CpiContext::new_with_signer(self.token_program.to_account_info(), cpi_accounts, &signer_seeds),
+            amount.min(self.treasury_vault.amount),
+        )
+    }
+}
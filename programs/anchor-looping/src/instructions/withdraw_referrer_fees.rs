@@ -0,0 +1,134 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{associated_token::AssociatedToken, token::{transfer, Mint, Token, TokenAccount, Transfer}};
+use crate::{constant::KAMINO_PROGRAM_ID, error::LoopingError, kamino::{withdraw_referrer_fees, WithdrawReferrerFees as KaminoWithdrawReferrerFees}, state::Config};
+
+#[derive(Accounts)]
+pub struct WithdrawReferrerFees<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ LoopingError::NotAdmin,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [b"referrer"],
+        bump,
+    )]
+    pub referrer: SystemAccount<'info>,
+    #[account(
+        seeds = [b"treasury"],
+        bump,
+    )]
+    pub treasury: SystemAccount<'info>,
+    pub reserve_liquidity_mint: Box<Account<'info, Mint>>,
+    #[account(
+        init_if_needed,
+        payer = admin,
+        associated_token::mint = reserve_liquidity_mint,
+        associated_token::authority = referrer,
+    )]
+    pub referrer_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = reserve_liquidity_mint,
+        associated_token::authority = treasury,
+    )]
+    pub treasury_vault: Box<Account<'info, TokenAccount>>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+
+    /// Kamino-specific accounts
+    #[account(
+        mut,
+        seeds = [
+            b"referrer_acc",
+            referrer.key().as_ref(),
+            reserve.key().as_ref(),
+        ],
+        bump,
+        seeds::program = KAMINO_PROGRAM_ID,
+    )]
+    /// CHECK: checked by the Kamino program
+    pub referrer_token_state: UncheckedAccount<'info>,
+    /// CHECK: checked by the Kamino program
+    pub lending_market: UncheckedAccount<'info>,
+    #[account(
+        seeds = [
+            b"lma", 
+            lending_market.key().as_ref()
+        ],
+        bump,
+        seeds::program = KAMINO_PROGRAM_ID,
+    )]
+    /// CHECK: checked by the Kamino program
+    pub lending_market_authority: UncheckedAccount<'info>,
+    #[account(mut)]
+    /// CHECK: checked by the Kamino program
+    pub reserve: UncheckedAccount<'info>,
+    #[account(mut)]
+    /// CHECK: checked by the Kamino program
+    pub reserve_supply_liquidity: UncheckedAccount<'info>,
+    #[account(address = KAMINO_PROGRAM_ID)]
+    /// CHECK: checked by the Kamino program
+    pub kamino_lending_program: AccountInfo<'info>,
+}
+
+impl<'info> WithdrawReferrerFees<'info> {
+    /// # Claim the referral fees from Kamino
+    /// 
+    /// Kamino only pays into a token account owned by the referrer, so they land in the referrer vault
+    /// first. Returns the amount claimed.
+    pub fn withdraw_referrer_fees(&mut self, bumps: &WithdrawReferrerFeesBumps) -> Result<u64> {
+        let signer_seeds: [&[&[u8]];1] = [&[
+            b"referrer".as_ref(),
+            &[bumps.referrer]
+        ]];
+
+        let balance_before = self.referrer_vault.amount;
+
+        withdraw_referrer_fees(
+            KaminoWithdrawReferrerFees {
+                referrer: self.referrer.to_account_info(),
+                referrer_token_state: self.referrer_token_state.to_account_info(),
+                reserve: self.reserve.to_account_info(),
+                reserve_liquidity_mint: self.reserve_liquidity_mint.to_account_info(),
+                reserve_supply_liquidity: self.reserve_supply_liquidity.to_account_info(),
+                referrer_token_account: self.referrer_vault.to_account_info(),
+                lending_market: self.lending_market.to_account_info(),
+                lending_market_authority: self.lending_market_authority.to_account_info(),
+                token_program: self.token_program.to_account_info(),
+                kamino_lending_program: self.kamino_lending_program.to_account_info(),
+            },
+            &signer_seeds,
+        )?;
+
+        self.referrer_vault.reload()?;
+
+        self.referrer_vault.amount.checked_sub(balance_before).ok_or(error!(LoopingError::MathOverflow))
+    }
+
+    /// # Move the claimed fees to the treasury
+    /// 
+    /// They are withdrawn from there with `withdraw_fees`, like the protocol fees.
+    pub fn transfer_to_treasury(&mut self, amount: u64, bumps: &WithdrawReferrerFeesBumps) -> Result<()> {
+        let signer_seeds: [&[&[u8]];1] = [&[
+            b"referrer".as_ref(),
+            &[bumps.referrer]
+        ]];
+
+        let cpi_accounts = Transfer {
+            from: self.referrer_vault.to_account_info(),
+            to: self.treasury_vault.to_account_info(),
+            authority: self.referrer.to_account_info(),
+        };
+
+        transfer(
+            CpiContext::new_with_signer(self.token_program.to_account_info(), cpi_accounts, &signer_seeds),
+            amount,
+        )
+    }
+}
@@ -0,0 +1,291 @@
+use anchor_lang::{prelude::*, solana_program::{instruction::Instruction, program::invoke_signed}};
+use anchor_spl::token::accessor;
+use crate::error::LoopingError;
+
+pub mod route;
+pub use route::*;
+
+pub const ROUTE_DISCRIMINATOR: [u8; 8] = [229, 23, 203, 151, 122, 227, 173, 42];
+pub const SHARED_ACCOUNTS_ROUTE_DISCRIMINATOR: [u8; 8] = [193, 32, 155, 51, 65, 214, 156, 129];
+pub const EXACT_OUT_ROUTE_DISCRIMINATOR: [u8; 8] = [208, 51, 239, 151, 123, 43, 237, 92];
+pub const SHARED_ACCOUNTS_EXACT_OUT_ROUTE_DISCRIMINATOR: [u8; 8] = [176, 209, 105, 168, 154, 125, 69, 62];
+
+/// Accounts of the Jupiter route instructions that are owned by our program, everything else
+/// (program authority, intermediate token accounts, AMM accounts) comes in as remaining accounts.
+pub struct SwapAccounts<'info> {
+    pub token_program: AccountInfo<'info>,
+    pub user_transfer_authority: AccountInfo<'info>,
+    pub source_token_account: AccountInfo<'info>,
+    pub destination_token_account: AccountInfo<'info>,
+    pub source_mint: AccountInfo<'info>,
+    pub destination_mint: AccountInfo<'info>,
+    pub event_authority: AccountInfo<'info>,
+    pub jupiter_program: AccountInfo<'info>,
+    /// Treasury token account Jupiter pays the platform fee into, only when the route carries one. Its mint
+    /// has to be the one the route takes the fee in, see `Route::platform_fee_mint`.
+    pub platform_fee_account: Option<AccountInfo<'info>>,
+}
+
+/// # Swap result
+/// 
+/// What a route CPI actually moved, measured on the vaults.
+pub struct SwapResult {
+    pub route_kind: RouteKind,
+    pub spent: u64,
+    pub received: u64,
+}
+
+/// # Check the route arguments
+/// 
+/// Decode the route arguments and perform an amount and slippage check on them. `amount` is the
+/// `in_amount` for exact-in routes and the `out_amount` for exact-out routes, `slippage_bps` is
+/// the slippage requested by the caller and has to fit under the market maximum. The platform fee of
+/// the route can't go above `max_platform_fee_bps`.
+pub fn check_swap_data(swap_data: &[u8], amount: u64, slippage_bps: u16, max_slippage_bps: u16, max_platform_fee_bps: u8) -> Result<Route> {
+    require_gte!(max_slippage_bps, slippage_bps, LoopingError::SlippageTooHigh);
+
+    let route = Route::decode(swap_data)?;
+    require_eq!(amount, route.exact_amount(), LoopingError::SwapAmountMismatch);
+    require_eq!(slippage_bps, route.slippage_bps, LoopingError::SlippageMismatch);
+    require_gte!(max_platform_fee_bps, route.platform_fee_bps, LoopingError::PlatformFeeTooHigh);
+
+    Ok(route)
+}
+
+/// # Check an exact-out route covering an amount
+/// 
+/// Like `check_swap_data`, for swaps whose output has to cover `min_out_amount` when the exact figure is
+/// only known on-chain (e.g. a debt repaid with the interest accrued up to the slot). Only exact-out routes
+/// are accepted, and their `out_amount` can be above `min_out_amount`.
+pub fn check_exact_out_swap_data(swap_data: &[u8], min_out_amount: u64, slippage_bps: u16, max_slippage_bps: u16, max_platform_fee_bps: u8) -> Result<Route> {
+    require_gte!(max_slippage_bps, slippage_bps, LoopingError::SlippageTooHigh);
+
+    let route = Route::decode(swap_data)?;
+    require!(route.mode == SwapMode::ExactOut, LoopingError::UnsupportedSwapRoute);
+    require_gte!(route.out_amount, min_out_amount, LoopingError::SwapAmountMismatch);
+    require_eq!(slippage_bps, route.slippage_bps, LoopingError::SlippageMismatch);
+    require_gte!(max_platform_fee_bps, route.platform_fee_bps, LoopingError::PlatformFeeTooHigh);
+
+    Ok(route)
+}
+
+/// # Check the swap result
+/// 
+/// `spent` and `received` are the balance deltas of the source and destination token accounts
+/// measured around the route CPI, they have to fit within the bounds of the decoded quote.
+pub fn check_swap_result(route: &Route, spent: u64, received: u64) -> Result<()> {
+    require_gte!(route.maximum_in_amount()?, spent, LoopingError::SwapInputTooHigh);
+    require_gte!(received, route.minimum_out_amount()?, LoopingError::SwapOutputTooLow);
+
+    Ok(())
+}
+
+/// # Swap through the route
+/// 
+/// Run the route CPI, exact-in or exact-out following the decoded `route`, and measure what it actually
+/// moves on the source and destination token accounts. The deltas are checked against the quote bounds,
+/// the price check stays with the caller since it needs the reserves of the pair. A platform fee account
+/// has to be in the mint the route takes the fee in.
+pub fn swap_route<'info>(
+    accounts: SwapAccounts<'info>,
+    route: &Route,
+    swap_data: &[u8],
+    remaining_accounts: &[AccountInfo<'info>],
+    signer_seeds: &[&[&[u8]]],
+) -> Result<SwapResult> {
+    if let Some(platform_fee_account) = &accounts.platform_fee_account {
+        let platform_fee_mint = route.platform_fee_mint(accounts.source_mint.key(), accounts.destination_mint.key());
+        require_keys_eq!(accessor::mint(platform_fee_account)?, platform_fee_mint, LoopingError::PlatformFeeMintMismatch);
+    }
+
+    let source_token_account = accounts.source_token_account.clone();
+    let destination_token_account = accounts.destination_token_account.clone();
+    let source_before = accessor::amount(&source_token_account)?;
+    let destination_before = accessor::amount(&destination_token_account)?;
+
+    match route.mode {
+        SwapMode::ExactIn => swap_exact_in(accounts, swap_data, remaining_accounts, signer_seeds)?,
+        SwapMode::ExactOut => swap_exact_out(accounts, swap_data, remaining_accounts, signer_seeds)?,
+    }
+
+    let spent = source_before.checked_sub(accessor::amount(&source_token_account)?).ok_or(error!(LoopingError::MathOverflow))?;
+    let received = accessor::amount(&destination_token_account)?.checked_sub(destination_before).ok_or(error!(LoopingError::MathOverflow))?;
+    check_swap_result(route, spent, received)?;
+
+    Ok(SwapResult { route_kind: route.kind, spent, received })
+}
+
+/// # Swap an exact input amount
+/// 
+/// Supports the `route` and `shared_accounts_route` instructions.
+pub fn swap_exact_in<'info>(
+    accounts: SwapAccounts<'info>,
+    swap_data: &[u8],
+    remaining_accounts: &[AccountInfo<'info>],
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    let (account_infos, metas) = match swap_data {
+        data if data.starts_with(&ROUTE_DISCRIMINATOR) => {
+            // Build the swap instruction accounts
+            let mut account_infos = vec![
+                accounts.token_program.clone(),
+                accounts.user_transfer_authority.clone(),
+                accounts.source_token_account.clone(),
+                accounts.destination_token_account.clone(),
+                accounts.destination_mint.clone(),
+                accounts.event_authority.clone(),
+                accounts.jupiter_program.clone(),
+            ];
+            account_infos.extend(accounts.platform_fee_account.iter().cloned());
+            account_infos.extend(remaining_accounts.iter().cloned());
+
+            let mut metas = vec![
+                AccountMeta::new_readonly(accounts.token_program.key(), false),             // token program
+                AccountMeta::new_readonly(accounts.user_transfer_authority.key(), true),    // user transfer authority
+                AccountMeta::new(accounts.source_token_account.key(), false),               // user source token account
+                AccountMeta::new(accounts.destination_token_account.key(), false),          // user destination token account
+                AccountMeta::new_readonly(accounts.jupiter_program.key(), false),           // [optional] destination token account
+                AccountMeta::new_readonly(accounts.destination_mint.key(), false),          // destination mint
+                platform_fee_meta(&accounts),                                               // [optional] platform fee account
+                AccountMeta::new_readonly(accounts.event_authority.key(), false),           // event authority
+                AccountMeta::new_readonly(accounts.jupiter_program.key(), false),           // jupiter program
+            ];
+            metas.extend(remaining_accounts.iter().map(to_remaining_meta));
+
+            (account_infos, metas)
+        }
+        data if data.starts_with(&SHARED_ACCOUNTS_ROUTE_DISCRIMINATOR) => shared_accounts(&accounts, remaining_accounts)?,
+        _ => return err!(LoopingError::UnsupportedSwapRoute),
+    };
+
+    invoke_route(&accounts, swap_data, account_infos, metas, signer_seeds)
+}
+
+/// # Swap for an exact output amount
+/// 
+/// Supports the `exact_out_route` and `shared_accounts_exact_out_route` instructions.
+pub fn swap_exact_out<'info>(
+    accounts: SwapAccounts<'info>,
+    swap_data: &[u8],
+    remaining_accounts: &[AccountInfo<'info>],
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    let (account_infos, metas) = match swap_data {
+        data if data.starts_with(&EXACT_OUT_ROUTE_DISCRIMINATOR) => {
+            let mut account_infos = vec![
+                accounts.token_program.clone(),
+                accounts.user_transfer_authority.clone(),
+                accounts.source_token_account.clone(),
+                accounts.destination_token_account.clone(),
+                accounts.source_mint.clone(),
+                accounts.destination_mint.clone(),
+                accounts.event_authority.clone(),
+                accounts.jupiter_program.clone(),
+            ];
+            account_infos.extend(accounts.platform_fee_account.iter().cloned());
+            account_infos.extend(remaining_accounts.iter().cloned());
+
+            let mut metas = vec![
+                AccountMeta::new_readonly(accounts.token_program.key(), false),             // token program
+                AccountMeta::new_readonly(accounts.user_transfer_authority.key(), true),    // user transfer authority
+                AccountMeta::new(accounts.source_token_account.key(), false),               // user source token account
+                AccountMeta::new(accounts.destination_token_account.key(), false),          // user destination token account
+                AccountMeta::new_readonly(accounts.jupiter_program.key(), false),           // [optional] destination token account
+                AccountMeta::new_readonly(accounts.source_mint.key(), false),               // source mint
+                AccountMeta::new_readonly(accounts.destination_mint.key(), false),          // destination mint
+                platform_fee_meta(&accounts),                                               // [optional] platform fee account
+                AccountMeta::new_readonly(accounts.jupiter_program.key(), false),           // [optional] token 2022 program
+                AccountMeta::new_readonly(accounts.event_authority.key(), false),           // event authority
+                AccountMeta::new_readonly(accounts.jupiter_program.key(), false),           // jupiter program
+            ];
+            metas.extend(remaining_accounts.iter().map(to_remaining_meta));
+
+            (account_infos, metas)
+        }
+        data if data.starts_with(&SHARED_ACCOUNTS_EXACT_OUT_ROUTE_DISCRIMINATOR) => shared_accounts(&accounts, remaining_accounts)?,
+        _ => return err!(LoopingError::UnsupportedSwapRoute),
+    };
+
+    invoke_route(&accounts, swap_data, account_infos, metas, signer_seeds)
+}
+
+/// The shared accounts variants have the same layout for both exact-in and exact-out.
+/// 
+/// Note: the client passes the program authority, program source token account and program destination
+/// token account as the first 3 remaining accounts.
+fn shared_accounts<'info>(
+    accounts: &SwapAccounts<'info>,
+    remaining_accounts: &[AccountInfo<'info>],
+) -> Result<(Vec<AccountInfo<'info>>, Vec<AccountMeta>)> {
+    require_gte!(remaining_accounts.len(), 3, LoopingError::InsufficientRemainingAccounts);
+
+    // Build the swap instruction accounts
+    let mut account_infos = vec![
+        accounts.token_program.clone(),
+        remaining_accounts[0].clone(),
+        accounts.user_transfer_authority.clone(),
+        accounts.source_token_account.clone(),
+        remaining_accounts[1].clone(),
+        remaining_accounts[2].clone(),
+        accounts.destination_token_account.clone(),
+        accounts.source_mint.clone(),
+        accounts.destination_mint.clone(),
+        accounts.event_authority.clone(),
+        accounts.jupiter_program.clone(),
+    ];
+    account_infos.extend(accounts.platform_fee_account.iter().cloned());
+    account_infos.extend(remaining_accounts.iter().cloned());
+
+    let mut metas = vec![
+        AccountMeta::new_readonly(accounts.token_program.key(), false),             // token program
+        AccountMeta::new_readonly(remaining_accounts[0].key(), false),              // program authority
+        AccountMeta::new_readonly(accounts.user_transfer_authority.key(), true),    // user transfer authority
+        AccountMeta::new(accounts.source_token_account.key(), false),               // source token account
+        AccountMeta::new(remaining_accounts[1].key(), false),                       // program source token account
+        AccountMeta::new(remaining_accounts[2].key(), false),                       // program destination token account
+        AccountMeta::new(accounts.destination_token_account.key(), false),          // destination token account
+        AccountMeta::new_readonly(accounts.source_mint.key(), false),               // source mint
+        AccountMeta::new_readonly(accounts.destination_mint.key(), false),          // destination mint
+        platform_fee_meta(accounts),                                                // [optional] platform fee account
+        AccountMeta::new_readonly(accounts.jupiter_program.key(), false),           // [optional] token 2022 program
+        AccountMeta::new_readonly(accounts.event_authority.key(), false),           // event authority
+        AccountMeta::new_readonly(accounts.jupiter_program.key(), false),           // jupiter program
+    ];
+    metas.extend(remaining_accounts.iter().skip(3).map(to_remaining_meta));
+
+    Ok((account_infos, metas))
+}
+
+/// The platform fee account when the route has one, the Jupiter program (Anchor's `None`) otherwise.
+fn platform_fee_meta(accounts: &SwapAccounts) -> AccountMeta {
+    match &accounts.platform_fee_account {
+        Some(platform_fee_account) => AccountMeta::new(platform_fee_account.key(), false),
+        None => AccountMeta::new_readonly(accounts.jupiter_program.key(), false),
+    }
+}
+
+fn to_remaining_meta(account: &AccountInfo) -> AccountMeta {
+    AccountMeta {
+        pubkey: *account.key,
+        is_signer: false,
+        is_writable: account.is_writable,
+    }
+}
+
+fn invoke_route<'info>(
+    accounts: &SwapAccounts<'info>,
+    swap_data: &[u8],
+    account_infos: Vec<AccountInfo<'info>>,
+    metas: Vec<AccountMeta>,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    let swap_ix = Instruction {
+        program_id: accounts.jupiter_program.key(),
+        accounts: metas,
+        data: swap_data.to_vec(),
+    };
+
+    invoke_signed(&swap_ix, &account_infos, signer_seeds)?;
+
+    Ok(())
+}
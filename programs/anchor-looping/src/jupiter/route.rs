@@ -0,0 +1,510 @@
+use anchor_lang::prelude::*;
+use crate::error::LoopingError;
+use super::{EXACT_OUT_ROUTE_DISCRIMINATOR, ROUTE_DISCRIMINATOR, SHARED_ACCOUNTS_EXACT_OUT_ROUTE_DISCRIMINATOR, SHARED_ACCOUNTS_ROUTE_DISCRIMINATOR};
+
+/// Args of the `route` instruction
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct RouteArgs {
+    pub route_plan: Vec<RoutePlanStep>,
+    pub in_amount: u64,
+    pub quoted_out_amount: u64,
+    pub slippage_bps: u16,
+    pub platform_fee_bps: u8,
+}
+
+/// Args of the `shared_accounts_route` instruction
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct SharedAccountsRouteArgs {
+    pub id: u8,
+    pub route_plan: Vec<RoutePlanStep>,
+    pub in_amount: u64,
+    pub quoted_out_amount: u64,
+    pub slippage_bps: u16,
+    pub platform_fee_bps: u8,
+}
+
+/// Args of the `exact_out_route` instruction
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct ExactOutRouteArgs {
+    pub route_plan: Vec<RoutePlanStep>,
+    pub out_amount: u64,
+    pub quoted_in_amount: u64,
+    pub slippage_bps: u16,
+    pub platform_fee_bps: u8,
+}
+
+/// Args of the `shared_accounts_exact_out_route` instruction
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct SharedAccountsExactOutRouteArgs {
+    pub id: u8,
+    pub route_plan: Vec<RoutePlanStep>,
+    pub out_amount: u64,
+    pub quoted_in_amount: u64,
+    pub slippage_bps: u16,
+    pub platform_fee_bps: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct RoutePlanStep {
+    pub swap: Swap,
+    pub percent: u8,
+    pub input_index: u8,
+    pub output_index: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    Bid,
+    Ask,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccountsType {
+    TransferHookA,
+    TransferHookB,
+    TransferHookReward,
+    TransferHookInput,
+    TransferHookIntermediate,
+    TransferHookOutput,
+    SupplementalTickArrays,
+    SupplementalTickArraysOne,
+    SupplementalTickArraysTwo,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct RemainingAccountsSlice {
+    pub accounts_type: AccountsType,
+    pub length: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct RemainingAccountsInfo {
+    pub slices: Vec<RemainingAccountsSlice>,
+}
+
+/// AMMs a route step can go through, in the order of the Jupiter v6 IDL (the variant index is
+/// the Borsh tag). Routes through an AMM we don't know about fail to decode.
+#[allow(clippy::enum_variant_names)]
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub enum Swap {
+    Saber,
+    SaberAddDecimalsDeposit,
+    SaberAddDecimalsWithdraw,
+    TokenSwap,
+    Sencha,
+    Step,
+    Cropper,
+    Raydium,
+    Crema { a_to_b: bool },
+    Lifinity,
+    Mercurial,
+    Cykura,
+    Serum { side: Side },
+    MarinadeDeposit,
+    MarinadeUnstake,
+    Aldrin { side: Side },
+    AldrinV2 { side: Side },
+    Whirlpool { a_to_b: bool },
+    Invariant { x_to_y: bool },
+    Meteora,
+    GooseFX,
+    DeltaFi { stable: bool },
+    Balansol,
+    MarcoPolo { x_to_y: bool },
+    Dradex { side: Side },
+    LifinityV2,
+    RaydiumClmm,
+    Openbook { side: Side },
+    Phoenix { side: Side },
+    Symmetry { from_token_id: u64, to_token_id: u64 },
+    TokenSwapV2,
+    HeliumTreasuryManagementRedeemV0,
+    StakeDexStakeWrappedSol,
+    StakeDexSwapViaStake { bridge_stake_seed: u32 },
+    GooseFXV2,
+    Perps,
+    PerpsAddLiquidity,
+    PerpsRemoveLiquidity,
+    MeteoraDlmm,
+    OpenBookV2 { side: Side },
+    RaydiumClmmV2,
+    StakeDexPrefundWithdrawStakeAndDepositStake { bridge_stake_seed: u32 },
+    Clone { pool_index: u8, quantity_is_input: bool, quantity_is_collateral: bool },
+    SanctumS { src_lst_value_calc_accs: u8, dst_lst_value_calc_accs: u8, src_lst_index: u32, dst_lst_index: u32 },
+    SanctumSAddLiquidity { lst_value_calc_accs: u8, lst_index: u32 },
+    SanctumSRemoveLiquidity { lst_value_calc_accs: u8, lst_index: u32 },
+    RaydiumCP,
+    WhirlpoolSwapV2 { a_to_b: bool, remaining_accounts_info: Option<RemainingAccountsInfo> },
+    OneIntro,
+    PumpdotfunWrappedBuy,
+    PumpdotfunWrappedSell,
+    PerpsV2,
+    PerpsV2AddLiquidity,
+    PerpsV2RemoveLiquidity,
+    MoonshotWrappedBuy,
+    MoonshotWrappedSell,
+    StabbleStableSwap,
+    StabbleWeightedSwap,
+    Obric { x_to_y: bool },
+    FoxBuyFromEstimatedCost,
+    FoxClaimPartial { is_y: bool },
+    SolFi { is_quote_to_base: bool },
+    SolayerDelegateNoInit,
+    SolayerUndelegateNoInit,
+    TokenMill { side: Side },
+    DaosFunBuy,
+    DaosFunSell,
+    ZeroFi,
+    StakeDexWithdrawWrappedSol,
+    VirtualsBuy,
+    VirtualsSell,
+    Perena { in_index: u8, out_index: u8 },
+    PumpdotfunAmmBuy,
+    PumpdotfunAmmSell,
+    Gamma,
+    MeteoraDlmmSwapV2 { remaining_accounts_info: RemainingAccountsInfo },
+    Woofi,
+    MeteoraDammV2,
+    MeteoraDynamicBondingCurveSwap,
+    StabbleStableSwapV2,
+    StabbleWeightedSwapV2,
+    RaydiumLaunchlabBuy { share_fee_rate: u64 },
+    RaydiumLaunchlabSell { share_fee_rate: u64 },
+    BoopdotfunWrappedBuy,
+    BoopdotfunWrappedSell,
+    Plasma { side: Side },
+    GoonFi { is_bid: bool, blacklist_bump: u8 },
+    HumidiFi { swap_id: u64, is_base_to_quote: bool },
+    MeteoraDynamicBondingCurveSwapWithRemainingAccounts,
+    TesseraV { side: Side },
+}
+
+/// Jupiter route instruction a swap went through
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RouteKind {
+    Route,
+    SharedAccountsRoute,
+    ExactOutRoute,
+    SharedAccountsExactOutRoute,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SwapMode {
+    ExactIn,
+    ExactOut,
+}
+
+/// # Decoded route
+/// 
+/// Common view over the args of the 4 supported route instructions. For exact-in routes
+/// `in_amount` is exact and `out_amount` is the quote, the other way around for exact-out routes.
+#[derive(Clone, Debug)]
+pub struct Route {
+    pub kind: RouteKind,
+    pub mode: SwapMode,
+    pub route_plan: Vec<RoutePlanStep>,
+    pub in_amount: u64,
+    pub out_amount: u64,
+    pub slippage_bps: u16,
+    pub platform_fee_bps: u8,
+}
+
+impl Route {
+    /// # Decode the route instruction data
+    /// 
+    /// The whole buffer has to be consumed, trailing bytes are treated as malformed data. A slippage
+    /// above 100% is malformed too.
+    pub fn decode(swap_data: &[u8]) -> Result<Self> {
+        require_gte!(swap_data.len(), 8, LoopingError::InvalidSwapData);
+        let (discriminator, args) = swap_data.split_at(8);
+
+        let route = match discriminator {
+            d if d == ROUTE_DISCRIMINATOR => {
+                let args = RouteArgs::try_from_slice(args).map_err(|_| LoopingError::InvalidSwapData)?;
+                Route {
+                    kind: RouteKind::Route,
+                    mode: SwapMode::ExactIn,
+                    route_plan: args.route_plan,
+                    in_amount: args.in_amount,
+                    out_amount: args.quoted_out_amount,
+                    slippage_bps: args.slippage_bps,
+                    platform_fee_bps: args.platform_fee_bps,
+                }
+            }
+            d if d == SHARED_ACCOUNTS_ROUTE_DISCRIMINATOR => {
+                let args = SharedAccountsRouteArgs::try_from_slice(args).map_err(|_| LoopingError::InvalidSwapData)?;
+                Route {
+                    kind: RouteKind::SharedAccountsRoute,
+                    mode: SwapMode::ExactIn,
+                    route_plan: args.route_plan,
+                    in_amount: args.in_amount,
+                    out_amount: args.quoted_out_amount,
+                    slippage_bps: args.slippage_bps,
+                    platform_fee_bps: args.platform_fee_bps,
+                }
+            }
+            d if d == EXACT_OUT_ROUTE_DISCRIMINATOR => {
+                let args = ExactOutRouteArgs::try_from_slice(args).map_err(|_| LoopingError::InvalidSwapData)?;
+                Route {
+                    kind: RouteKind::ExactOutRoute,
+                    mode: SwapMode::ExactOut,
+                    route_plan: args.route_plan,
+                    in_amount: args.quoted_in_amount,
+                    out_amount: args.out_amount,
+                    slippage_bps: args.slippage_bps,
+                    platform_fee_bps: args.platform_fee_bps,
+                }
+            }
+            d if d == SHARED_ACCOUNTS_EXACT_OUT_ROUTE_DISCRIMINATOR => {
+                let args = SharedAccountsExactOutRouteArgs::try_from_slice(args).map_err(|_| LoopingError::InvalidSwapData)?;
+                Route {
+                    kind: RouteKind::SharedAccountsExactOutRoute,
+                    mode: SwapMode::ExactOut,
+                    route_plan: args.route_plan,
+                    in_amount: args.quoted_in_amount,
+                    out_amount: args.out_amount,
+                    slippage_bps: args.slippage_bps,
+                    platform_fee_bps: args.platform_fee_bps,
+                }
+            }
+            _ => return err!(LoopingError::UnsupportedSwapRoute),
+        };
+        require!(!route.route_plan.is_empty(), LoopingError::InvalidSwapData);
+        require_gte!(10_000, route.slippage_bps, LoopingError::InvalidSwapData);
+
+        Ok(route)
+    }
+
+    /// Least amount the swap can deliver: the exact `out_amount` or the quote minus the slippage.
+    pub fn minimum_out_amount(&self) -> Result<u64> {
+        match self.mode {
+            SwapMode::ExactIn => {
+                let keep_bps = 10_000u128.checked_sub(self.slippage_bps as u128).ok_or(error!(LoopingError::InvalidSwapData))?;
+                Ok((self.out_amount as u128 * keep_bps / 10_000) as u64)
+            }
+            SwapMode::ExactOut => Ok(self.out_amount),
+        }
+    }
+
+    /// Most the swap can spend: the exact `in_amount` or the quote plus the slippage.
+    pub fn maximum_in_amount(&self) -> Result<u64> {
+        match self.mode {
+            SwapMode::ExactIn => Ok(self.in_amount),
+            SwapMode::ExactOut => {
+                let maximum_in_amount = (self.in_amount as u128 * (10_000 + self.slippage_bps as u128)).div_ceil(10_000);
+                u64::try_from(maximum_in_amount).map_err(|_| error!(LoopingError::MathOverflow))
+            }
+        }
+    }
+
+    /// Exact side of the swap: `in_amount` for exact-in routes, `out_amount` for exact-out routes.
+    pub fn exact_amount(&self) -> u64 {
+        match self.mode {
+            SwapMode::ExactIn => self.in_amount,
+            SwapMode::ExactOut => self.out_amount,
+        }
+    }
+
+    /// Mint Jupiter takes the platform fee in: the output mint of exact-in routes, the input mint of exact-out routes.
+    pub fn platform_fee_mint(&self, input_mint: Pubkey, output_mint: Pubkey) -> Pubkey {
+        match self.mode {
+            SwapMode::ExactIn => output_mint,
+            SwapMode::ExactOut => input_mint,
+        }
+    }
+}
+
+/// # Resize an exact-in route
+/// 
+/// Rewrite the `in_amount` of exact-in route data, for instructions that only know the amount to swap
+/// on-chain. The quoted out amount is scaled along, so the route keeps the slippage bound the client 
+/// asked for around its own quote. Exact-out routes are refused.
+pub fn resize_exact_in_route(swap_data: &[u8], in_amount: u64) -> Result<Vec<u8>> {
+    require_gte!(swap_data.len(), 8, LoopingError::InvalidSwapData);
+    let (discriminator, args) = swap_data.split_at(8);
+
+    let mut data = discriminator.to_vec();
+    match discriminator {
+        d if d == ROUTE_DISCRIMINATOR => {
+            let mut args = RouteArgs::try_from_slice(args).map_err(|_| LoopingError::InvalidSwapData)?;
+            args.quoted_out_amount = scale_quote(args.quoted_out_amount, args.in_amount, in_amount)?;
+            args.in_amount = in_amount;
+            args.serialize(&mut data)?;
+        }
+        d if d == SHARED_ACCOUNTS_ROUTE_DISCRIMINATOR => {
+            let mut args = SharedAccountsRouteArgs::try_from_slice(args).map_err(|_| LoopingError::InvalidSwapData)?;
+            args.quoted_out_amount = scale_quote(args.quoted_out_amount, args.in_amount, in_amount)?;
+            args.in_amount = in_amount;
+            args.serialize(&mut data)?;
+        }
+        _ => return err!(LoopingError::UnsupportedSwapRoute),
+    }
+
+    Ok(data)
+}
+
+/// `quote` for `amount` instead of `quoted_amount`, rounded down.
+fn scale_quote(quote: u64, quoted_amount: u64, amount: u64) -> Result<u64> {
+    let scaled = (quote as u128)
+        .checked_mul(amount as u128)
+        .and_then(|value| value.checked_div(quoted_amount as u128))
+        .ok_or(error!(LoopingError::InvalidSwapData))?;
+
+    u64::try_from(scaled).map_err(|_| error!(LoopingError::MathOverflow))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route_plan() -> Vec<RoutePlanStep> {
+        vec![RoutePlanStep { swap: Swap::Whirlpool { a_to_b: true }, percent: 100, input_index: 0, output_index: 1 }]
+    }
+
+    fn encode(discriminator: [u8; 8], args: impl AnchorSerialize) -> Vec<u8> {
+        let mut data = discriminator.to_vec();
+        args.serialize(&mut data).unwrap();
+        data
+    }
+
+    fn route_data(slippage_bps: u16) -> Vec<u8> {
+        encode(ROUTE_DISCRIMINATOR, RouteArgs {
+            route_plan: route_plan(),
+            in_amount: 1_000,
+            quoted_out_amount: 2_000,
+            slippage_bps,
+            platform_fee_bps: 0,
+        })
+    }
+
+    #[test]
+    fn decode_route() {
+        let route = Route::decode(&route_data(50)).unwrap();
+
+        assert_eq!(route.kind, RouteKind::Route);
+        assert_eq!(route.mode, SwapMode::ExactIn);
+        assert_eq!(route.in_amount, 1_000);
+        assert_eq!(route.out_amount, 2_000);
+        assert_eq!(route.exact_amount(), 1_000);
+        assert_eq!(route.minimum_out_amount().unwrap(), 1_990);
+        assert_eq!(route.maximum_in_amount().unwrap(), 1_000);
+    }
+
+    #[test]
+    fn decode_shared_accounts_route() {
+        let data = encode(SHARED_ACCOUNTS_ROUTE_DISCRIMINATOR, SharedAccountsRouteArgs {
+            id: 3,
+            route_plan: route_plan(),
+            in_amount: 1_000,
+            quoted_out_amount: 2_000,
+            slippage_bps: 100,
+            platform_fee_bps: 20,
+        });
+        let route = Route::decode(&data).unwrap();
+
+        assert_eq!(route.kind, RouteKind::SharedAccountsRoute);
+        assert_eq!(route.mode, SwapMode::ExactIn);
+        assert_eq!(route.exact_amount(), 1_000);
+        assert_eq!(route.platform_fee_bps, 20);
+        assert_eq!(route.minimum_out_amount().unwrap(), 1_980);
+    }
+
+    #[test]
+    fn decode_exact_out_route() {
+        let data = encode(EXACT_OUT_ROUTE_DISCRIMINATOR, ExactOutRouteArgs {
+            route_plan: route_plan(),
+            out_amount: 2_000,
+            quoted_in_amount: 1_000,
+            slippage_bps: 50,
+            platform_fee_bps: 0,
+        });
+        let route = Route::decode(&data).unwrap();
+
+        assert_eq!(route.kind, RouteKind::ExactOutRoute);
+        assert_eq!(route.mode, SwapMode::ExactOut);
+        assert_eq!(route.exact_amount(), 2_000);
+        assert_eq!(route.minimum_out_amount().unwrap(), 2_000);
+        assert_eq!(route.maximum_in_amount().unwrap(), 1_005);
+    }
+
+    #[test]
+    fn decode_shared_accounts_exact_out_route() {
+        let data = encode(SHARED_ACCOUNTS_EXACT_OUT_ROUTE_DISCRIMINATOR, SharedAccountsExactOutRouteArgs {
+            id: 0,
+            route_plan: route_plan(),
+            out_amount: 2_000,
+            quoted_in_amount: 999,
+            slippage_bps: 100,
+            platform_fee_bps: 0,
+        });
+        let route = Route::decode(&data).unwrap();
+
+        assert_eq!(route.kind, RouteKind::SharedAccountsExactOutRoute);
+        assert_eq!(route.mode, SwapMode::ExactOut);
+        assert_eq!(route.exact_amount(), 2_000);
+        // 999 * 1.01 = 1008.99, rounded up
+        assert_eq!(route.maximum_in_amount().unwrap(), 1_009);
+    }
+
+    #[test]
+    fn decode_rejects_truncated_data() {
+        let data = route_data(50);
+
+        assert_eq!(Route::decode(&data[..data.len() - 1]).unwrap_err(), error!(LoopingError::InvalidSwapData));
+        assert_eq!(Route::decode(&data[..4]).unwrap_err(), error!(LoopingError::InvalidSwapData));
+    }
+
+    #[test]
+    fn decode_rejects_trailing_bytes() {
+        let mut data = route_data(50);
+        data.push(0);
+
+        assert_eq!(Route::decode(&data).unwrap_err(), error!(LoopingError::InvalidSwapData));
+    }
+
+    #[test]
+    fn decode_rejects_unknown_discriminator() {
+        let mut data = route_data(50);
+        data[..8].copy_from_slice(&[0; 8]);
+
+        assert_eq!(Route::decode(&data).unwrap_err(), error!(LoopingError::UnsupportedSwapRoute));
+    }
+
+    #[test]
+    fn decode_rejects_slippage_above_100_percent() {
+        assert!(Route::decode(&route_data(10_000)).is_ok());
+        assert_eq!(Route::decode(&route_data(10_001)).unwrap_err(), error!(LoopingError::InvalidSwapData));
+    }
+
+    #[test]
+    fn exact_in_route_takes_the_platform_fee_in_the_output_mint() {
+        let (input_mint, output_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let route = Route::decode(&route_data(50)).unwrap();
+
+        assert_eq!(route.platform_fee_mint(input_mint, output_mint), output_mint);
+    }
+
+    #[test]
+    fn exact_out_route_takes_the_platform_fee_in_the_input_mint() {
+        let (input_mint, output_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let data = encode(EXACT_OUT_ROUTE_DISCRIMINATOR, ExactOutRouteArgs {
+            route_plan: route_plan(),
+            out_amount: 2_000,
+            quoted_in_amount: 1_000,
+            slippage_bps: 50,
+            platform_fee_bps: 20,
+        });
+        let route = Route::decode(&data).unwrap();
+
+        assert_eq!(route.platform_fee_mint(input_mint, output_mint), input_mint);
+    }
+
+    #[test]
+    fn resize_exact_in_route_scales_the_quote() {
+        let route = Route::decode(&resize_exact_in_route(&route_data(50), 1_500).unwrap()).unwrap();
+
+        assert_eq!(route.in_amount, 1_500);
+        assert_eq!(route.out_amount, 3_000);
+        assert_eq!(route.slippage_bps, 50);
+    }
+}
@@ -0,0 +1,711 @@
+use anchor_lang::{prelude::*, solana_program::{instruction::Instruction, program::invoke_signed}};
+
+pub const INIT_USER_METADATA_DISCRIMINATOR: [u8; 8] = [117, 169, 176, 69, 197, 23, 15, 162];
+pub const INIT_OBLIGATION_DISCRIMINATOR: [u8; 8] = [251, 10, 231, 76, 27, 11, 159, 96];
+pub const INIT_OBLIGATION_FARMS_FOR_RESERVE_DISCRIMINATOR: [u8; 8] = [136, 63, 15, 186, 211, 152, 168, 164];
+pub const REFRESH_RESERVE_DISCRIMINATOR: [u8; 8] = [2, 218, 138, 235, 79, 201, 25, 102];
+pub const REFRESH_OBLIGATION_DISCRIMINATOR: [u8; 8] = [33, 132, 147, 228, 151, 192, 72, 89];
+pub const DEPOSIT_RESERVE_LIQUIDITY_AND_OBLIGATION_COLLATERAL_V2_DISCRIMINATOR: [u8; 8] = [216, 224, 191, 27, 204, 151, 102, 175];
+pub const BORROW_OBLIGATION_LIQUIDITY_V2_DISCRIMINATOR: [u8; 8] = [161, 128, 143, 245, 171, 199, 194, 6];
+pub const WITHDRAW_OBLIGATION_COLLATERAL_AND_REDEEM_RESERVE_COLLATERAL_V2_DISCRIMINATOR: [u8; 8] = [235, 52, 119, 152, 149, 197, 20, 7];
+pub const REPAY_OBLIGATION_LIQUIDITY_V2_DISCRIMINATOR: [u8; 8] = [116, 174, 213, 76, 180, 53, 210, 144];
+pub const INIT_REFERRER_TOKEN_STATE_DISCRIMINATOR: [u8; 8] = [116, 45, 66, 148, 58, 13, 218, 115];
+pub const WITHDRAW_REFERRER_FEES_DISCRIMINATOR: [u8; 8] = [171, 118, 121, 201, 233, 140, 23, 228];
+pub const HARVEST_REWARD_DISCRIMINATOR: [u8; 8] = [68, 200, 228, 233, 184, 32, 226, 188];
+pub const FLASH_BORROW_RESERVE_LIQUIDITY_DISCRIMINATOR: [u8; 8] = [135, 231, 52, 167, 7, 52, 212, 193];
+pub const FLASH_REPAY_RESERVE_LIQUIDITY_DISCRIMINATOR: [u8; 8] = [185, 117, 0, 203, 96, 245, 180, 186];
+
+/// Accounts of `init_user_metadata`
+pub struct InitUserMetadata<'info> {
+    pub owner: AccountInfo<'info>,
+    pub fee_payer: AccountInfo<'info>,
+    pub user_metadata: AccountInfo<'info>,
+    pub referrer_user_metadata: Option<AccountInfo<'info>>,
+    pub rent: AccountInfo<'info>,
+    pub system_program: AccountInfo<'info>,
+    pub kamino_lending_program: AccountInfo<'info>,
+}
+
+/// Accounts of `init_obligation`
+pub struct InitObligation<'info> {
+    pub obligation_owner: AccountInfo<'info>,
+    pub fee_payer: AccountInfo<'info>,
+    pub obligation: AccountInfo<'info>,
+    pub lending_market: AccountInfo<'info>,
+    pub seed1_account: AccountInfo<'info>,
+    pub seed2_account: AccountInfo<'info>,
+    pub owner_user_metadata: AccountInfo<'info>,
+    pub rent: AccountInfo<'info>,
+    pub system_program: AccountInfo<'info>,
+    pub kamino_lending_program: AccountInfo<'info>,
+}
+
+/// Accounts of `init_obligation_farms_for_reserve`
+pub struct InitObligationFarmsForReserve<'info> {
+    pub payer: AccountInfo<'info>,
+    pub owner: AccountInfo<'info>,
+    pub obligation: AccountInfo<'info>,
+    pub lending_market_authority: AccountInfo<'info>,
+    pub reserve: AccountInfo<'info>,
+    pub reserve_farm_state: AccountInfo<'info>,
+    pub obligation_farm: AccountInfo<'info>,
+    pub lending_market: AccountInfo<'info>,
+    pub farms_program: AccountInfo<'info>,
+    pub rent: AccountInfo<'info>,
+    pub system_program: AccountInfo<'info>,
+    pub kamino_lending_program: AccountInfo<'info>,
+}
+
+/// Accounts of `refresh_reserve`
+///
+/// We are going to only use scope as oracle (this is Kamino in-house oracle that doesn't require any crank
+/// since they do it for you)
+pub struct RefreshReserve<'info> {
+    pub reserve: AccountInfo<'info>,
+    pub lending_market: AccountInfo<'info>,
+    pub scope_oracle: AccountInfo<'info>,
+    pub kamino_lending_program: AccountInfo<'info>,
+}
+
+/// Accounts of `refresh_obligation`, the reserves of the obligation come on top of these
+pub struct RefreshObligation<'info> {
+    pub lending_market: AccountInfo<'info>,
+    pub obligation: AccountInfo<'info>,
+    pub kamino_lending_program: AccountInfo<'info>,
+}
+
+/// Accounts of `deposit_reserve_liquidity_and_obligation_collateral_v2`
+pub struct DepositReserveLiquidityAndObligationCollateralV2<'info> {
+    pub owner: AccountInfo<'info>,
+    pub obligation: AccountInfo<'info>,
+    pub lending_market: AccountInfo<'info>,
+    pub lending_market_authority: AccountInfo<'info>,
+    pub reserve: AccountInfo<'info>,
+    pub reserve_liquidity_mint: AccountInfo<'info>,
+    pub reserve_liquidity_supply: AccountInfo<'info>,
+    pub reserve_collateral_mint: AccountInfo<'info>,
+    pub reserve_destination_deposit_collateral: AccountInfo<'info>,
+    pub user_source_liquidity: AccountInfo<'info>,
+    pub token_program: AccountInfo<'info>,
+    pub instruction_sysvar_account: AccountInfo<'info>,
+    pub obligation_farm_user_state: Option<AccountInfo<'info>>,
+    pub reserve_farm_state: Option<AccountInfo<'info>>,
+    pub farms_program: AccountInfo<'info>,
+    pub kamino_lending_program: AccountInfo<'info>,
+}
+
+/// Accounts of `borrow_obligation_liquidity_v2`
+pub struct BorrowObligationLiquidityV2<'info> {
+    pub owner: AccountInfo<'info>,
+    pub obligation: AccountInfo<'info>,
+    pub lending_market: AccountInfo<'info>,
+    pub lending_market_authority: AccountInfo<'info>,
+    pub borrow_reserve: AccountInfo<'info>,
+    pub borrow_reserve_liquidity_mint: AccountInfo<'info>,
+    pub reserve_source_liquidity: AccountInfo<'info>,
+    pub borrow_reserve_liquidity_fee_receiver: AccountInfo<'info>,
+    pub user_destination_liquidity: AccountInfo<'info>,
+    pub referrer_token_state: Option<AccountInfo<'info>>,
+    pub token_program: AccountInfo<'info>,
+    pub instruction_sysvar_account: AccountInfo<'info>,
+    pub obligation_farm_user_state: Option<AccountInfo<'info>>,
+    pub reserve_farm_state: Option<AccountInfo<'info>>,
+    pub farms_program: AccountInfo<'info>,
+    pub kamino_lending_program: AccountInfo<'info>,
+}
+
+/// Accounts of `withdraw_obligation_collateral_and_redeem_reserve_collateral_v2`
+pub struct WithdrawObligationCollateralAndRedeemReserveCollateralV2<'info> {
+    pub owner: AccountInfo<'info>,
+    pub obligation: AccountInfo<'info>,
+    pub lending_market: AccountInfo<'info>,
+    pub lending_market_authority: AccountInfo<'info>,
+    pub withdraw_reserve: AccountInfo<'info>,
+    pub reserve_liquidity_mint: AccountInfo<'info>,
+    pub reserve_source_collateral: AccountInfo<'info>,
+    pub reserve_collateral_mint: AccountInfo<'info>,
+    pub reserve_liquidity_supply: AccountInfo<'info>,
+    pub user_destination_liquidity: AccountInfo<'info>,
+    pub token_program: AccountInfo<'info>,
+    pub instruction_sysvar_account: AccountInfo<'info>,
+    pub obligation_farm_user_state: Option<AccountInfo<'info>>,
+    pub reserve_farm_state: Option<AccountInfo<'info>>,
+    pub farms_program: AccountInfo<'info>,
+    pub kamino_lending_program: AccountInfo<'info>,
+}
+
+/// Accounts of `repay_obligation_liquidity_v2`
+pub struct RepayObligationLiquidityV2<'info> {
+    pub owner: AccountInfo<'info>,
+    pub obligation: AccountInfo<'info>,
+    pub lending_market: AccountInfo<'info>,
+    pub repay_reserve: AccountInfo<'info>,
+    pub reserve_liquidity_mint: AccountInfo<'info>,
+    pub reserve_destination_liquidity: AccountInfo<'info>,
+    pub user_source_liquidity: AccountInfo<'info>,
+    pub token_program: AccountInfo<'info>,
+    pub instruction_sysvar_account: AccountInfo<'info>,
+    pub obligation_farm_user_state: Option<AccountInfo<'info>>,
+    pub reserve_farm_state: Option<AccountInfo<'info>>,
+    pub lending_market_authority: AccountInfo<'info>,
+    pub farms_program: AccountInfo<'info>,
+    pub kamino_lending_program: AccountInfo<'info>,
+}
+
+/// Accounts of `init_referrer_token_state`
+pub struct InitReferrerTokenState<'info> {
+    pub payer: AccountInfo<'info>,
+    pub lending_market: AccountInfo<'info>,
+    pub reserve: AccountInfo<'info>,
+    pub referrer: AccountInfo<'info>,
+    pub referrer_token_state: AccountInfo<'info>,
+    pub rent: AccountInfo<'info>,
+    pub system_program: AccountInfo<'info>,
+    pub kamino_lending_program: AccountInfo<'info>,
+}
+
+/// Accounts of `withdraw_referrer_fees`
+pub struct WithdrawReferrerFees<'info> {
+    pub referrer: AccountInfo<'info>,
+    pub referrer_token_state: AccountInfo<'info>,
+    pub reserve: AccountInfo<'info>,
+    pub reserve_liquidity_mint: AccountInfo<'info>,
+    pub reserve_supply_liquidity: AccountInfo<'info>,
+    pub referrer_token_account: AccountInfo<'info>,
+    pub lending_market: AccountInfo<'info>,
+    pub lending_market_authority: AccountInfo<'info>,
+    pub token_program: AccountInfo<'info>,
+    pub kamino_lending_program: AccountInfo<'info>,
+}
+
+/// Accounts of `harvest_reward` (Kamino Farms program)
+pub struct HarvestReward<'info> {
+    pub owner: AccountInfo<'info>,
+    pub user_state: AccountInfo<'info>,
+    pub farm_state: AccountInfo<'info>,
+    pub global_config: AccountInfo<'info>,
+    pub reward_mint: AccountInfo<'info>,
+    pub user_reward_ata: AccountInfo<'info>,
+    pub rewards_vault: AccountInfo<'info>,
+    pub rewards_treasury_vault: AccountInfo<'info>,
+    pub farm_vaults_authority: AccountInfo<'info>,
+    pub scope_prices: Option<AccountInfo<'info>>,
+    pub token_program: AccountInfo<'info>,
+    pub farms_program: AccountInfo<'info>,
+}
+
+/// # Set up the user metadata account
+///
+/// This is a step needed for all wallets that use the Kamino program.
+pub fn init_user_metadata<'info>(accounts: InitUserMetadata<'info>, user_lookup_table: Pubkey, signer_seeds: &[&[&[u8]]]) -> Result<()> {
+    let program = &accounts.kamino_lending_program;
+
+    let metas = vec![
+        AccountMeta::new_readonly(accounts.owner.key(), true),                      // owner
+        AccountMeta::new(accounts.fee_payer.key(), true),                           // fee_payer
+        AccountMeta::new(accounts.user_metadata.key(), false),                      // user_metadata
+        optional_meta(&accounts.referrer_user_metadata, program, false),            // [optional] referrer_user_metadata
+        AccountMeta::new_readonly(accounts.rent.key(), false),                      // rent
+        AccountMeta::new_readonly(accounts.system_program.key(), false),            // system_program
+    ];
+
+    let account_infos = vec![
+        accounts.owner.clone(),
+        accounts.fee_payer.clone(),
+        accounts.user_metadata.clone(),
+        optional_info(&accounts.referrer_user_metadata, program),
+        accounts.rent.clone(),
+        accounts.system_program.clone(),
+    ];
+
+    invoke_kamino(
+        program,
+        metas,
+        &account_infos,
+        [INIT_USER_METADATA_DISCRIMINATOR.as_ref(), user_lookup_table.as_ref()].concat(),
+        signer_seeds,
+    )
+}
+
+/// # Set up the obligation account
+///
+/// This is the main account that will be used to borrow and repay assets.
+pub fn init_obligation<'info>(accounts: InitObligation<'info>, tag: u8, id: u8, signer_seeds: &[&[&[u8]]]) -> Result<()> {
+    let metas = vec![
+        AccountMeta::new_readonly(accounts.obligation_owner.key(), true),           // obligation_owner
+        AccountMeta::new(accounts.fee_payer.key(), true),                           // fee_payer
+        AccountMeta::new(accounts.obligation.key(), false),                         // obligation
+        AccountMeta::new_readonly(accounts.lending_market.key(), false),            // lending_market
+        AccountMeta::new_readonly(accounts.seed1_account.key(), false),             // seed1_account
+        AccountMeta::new_readonly(accounts.seed2_account.key(), false),             // seed2_account
+        AccountMeta::new_readonly(accounts.owner_user_metadata.key(), false),       // owner_user_metadata
+        AccountMeta::new_readonly(accounts.rent.key(), false),                      // rent
+        AccountMeta::new_readonly(accounts.system_program.key(), false),            // system_program
+    ];
+
+    let account_infos = vec![
+        accounts.obligation_owner.clone(),
+        accounts.fee_payer.clone(),
+        accounts.obligation.clone(),
+        accounts.lending_market.clone(),
+        accounts.seed1_account.clone(),
+        accounts.seed2_account.clone(),
+        accounts.owner_user_metadata.clone(),
+        accounts.rent.clone(),
+        accounts.system_program.clone(),
+    ];
+
+    invoke_kamino(
+        &accounts.kamino_lending_program,
+        metas,
+        &account_infos,
+        [INIT_OBLIGATION_DISCRIMINATOR.as_ref(), &[tag], &[id]].concat(),
+        signer_seeds,
+    )
+}
+
+/// # Set up the obligation farms for the reserve
+///
+/// This is an account that is needed only if there is a farm on the reserve.
+pub fn init_obligation_farms_for_reserve(accounts: InitObligationFarmsForReserve, mode: u8) -> Result<()> {
+    let metas = vec![
+        AccountMeta::new(accounts.payer.key(), true),                               // payer
+        AccountMeta::new_readonly(accounts.owner.key(), false),                     // owner
+        AccountMeta::new(accounts.obligation.key(), false),                         // obligation
+        AccountMeta::new_readonly(accounts.lending_market_authority.key(), false),  // lending_market_authority
+        AccountMeta::new(accounts.reserve.key(), false),                            // reserve
+        AccountMeta::new(accounts.reserve_farm_state.key(), false),                 // reserve_farm_state
+        AccountMeta::new(accounts.obligation_farm.key(), false),                    // obligation_farm
+        AccountMeta::new_readonly(accounts.lending_market.key(), false),            // lending_market
+        AccountMeta::new_readonly(accounts.farms_program.key(), false),             // farms_program
+        AccountMeta::new_readonly(accounts.rent.key(), false),                      // rent
+        AccountMeta::new_readonly(accounts.system_program.key(), false),            // system_program
+    ];
+
+    let account_infos = vec![
+        accounts.payer.clone(),
+        accounts.owner.clone(),
+        accounts.obligation.clone(),
+        accounts.lending_market_authority.clone(),
+        accounts.reserve.clone(),
+        accounts.reserve_farm_state.clone(),
+        accounts.obligation_farm.clone(),
+        accounts.lending_market.clone(),
+        accounts.farms_program.clone(),
+        accounts.rent.clone(),
+        accounts.system_program.clone(),
+    ];
+
+    invoke_kamino(
+        &accounts.kamino_lending_program,
+        metas,
+        &account_infos,
+        [INIT_OBLIGATION_FARMS_FOR_RESERVE_DISCRIMINATOR.as_ref(), &[mode]].concat(),
+        &[],
+    )
+}
+
+/// # Refresh a reserve
+///
+/// This is a step needed to refresh a reserve before interacting with it.
+pub fn refresh_reserve(accounts: RefreshReserve) -> Result<()> {
+    let program = &accounts.kamino_lending_program;
+
+    let metas = vec![
+        AccountMeta::new(accounts.reserve.key(), false),                            // reserve
+        AccountMeta::new_readonly(accounts.lending_market.key(), false),            // lending_market
+        AccountMeta::new_readonly(program.key(), false),                            // [optional] pyth_oracle
+        AccountMeta::new_readonly(program.key(), false),                            // [optional] switchboard_price_oracle
+        AccountMeta::new_readonly(program.key(), false),                            // [optional] switchboard_twap_oracle
+        AccountMeta::new_readonly(accounts.scope_oracle.key(), false),              // [optional] scope_prices
+    ];
+
+    let account_infos = vec![
+        accounts.reserve.clone(),
+        accounts.lending_market.clone(),
+        program.clone(),
+        accounts.scope_oracle.clone(),
+    ];
+
+    invoke_kamino(
+        program,
+        metas,
+        &account_infos,
+        REFRESH_RESERVE_DISCRIMINATOR.to_vec(),
+        &[],
+    )
+}
+
+/// # Refresh the obligation
+///
+/// `reserves` are the deposit reserves followed by the borrow reserves of the obligation, in the
+/// order the obligation stores them. Each of them has to be refreshed in the same slot first.
+pub fn refresh_obligation<'info>(accounts: RefreshObligation<'info>, reserves: &[AccountInfo<'info>]) -> Result<()> {
+    let mut metas = vec![
+        AccountMeta::new_readonly(accounts.lending_market.key(), false),            // lending_market
+        AccountMeta::new(accounts.obligation.key(), false),                         // obligation
+    ];
+    metas.extend(reserves.iter().map(|reserve| AccountMeta::new_readonly(reserve.key(), false)));
+
+    let mut account_infos = vec![
+        accounts.lending_market.clone(),
+        accounts.obligation.clone(),
+    ];
+    account_infos.extend(reserves.iter().cloned());
+
+    invoke_kamino(
+        &accounts.kamino_lending_program,
+        metas,
+        &account_infos,
+        REFRESH_OBLIGATION_DISCRIMINATOR.to_vec(),
+        &[],
+    )
+}
+
+/// # Deposit liquidity as obligation collateral
+pub fn deposit_reserve_liquidity_and_obligation_collateral_v2(accounts: DepositReserveLiquidityAndObligationCollateralV2, amount: u64, signer_seeds: &[&[&[u8]]]) -> Result<()> {
+    let program = &accounts.kamino_lending_program;
+
+    let metas = vec![
+        AccountMeta::new(accounts.owner.key(), true),                                       // owner
+        AccountMeta::new(accounts.obligation.key(), false),                                 // obligation
+        AccountMeta::new_readonly(accounts.lending_market.key(), false),                    // lending_market
+        AccountMeta::new_readonly(accounts.lending_market_authority.key(), false),          // lending_market_authority
+        AccountMeta::new(accounts.reserve.key(), false),                                    // reserve
+        AccountMeta::new_readonly(accounts.reserve_liquidity_mint.key(), false),            // reserve_liquidity_mint
+        AccountMeta::new(accounts.reserve_liquidity_supply.key(), false),                   // reserve_liquidity_supply
+        AccountMeta::new(accounts.reserve_collateral_mint.key(), false),                    // reserve_collateral_mint
+        AccountMeta::new(accounts.reserve_destination_deposit_collateral.key(), false),     // reserve_destination_deposit_collateral
+        AccountMeta::new(accounts.user_source_liquidity.key(), false),                      // user_source_liquidity
+        AccountMeta::new_readonly(program.key(), false),                                    // [optional] placeholder_user_destination_collateral
+        AccountMeta::new_readonly(accounts.token_program.key(), false),                     // collateral_token_program
+        AccountMeta::new_readonly(accounts.token_program.key(), false),                     // liquidity_token_program
+        AccountMeta::new_readonly(accounts.instruction_sysvar_account.key(), false),        // instruction_sysvar_account
+        optional_meta(&accounts.obligation_farm_user_state, program, true),                 // [optional] obligation_farm_user_state
+        optional_meta(&accounts.reserve_farm_state, program, true),                         // [optional] reserve_farm_state
+        AccountMeta::new_readonly(accounts.farms_program.key(), false),                     // farms_program
+    ];
+
+    let account_infos = vec![
+        accounts.owner.clone(),
+        accounts.obligation.clone(),
+        accounts.lending_market.clone(),
+        accounts.lending_market_authority.clone(),
+        accounts.reserve.clone(),
+        accounts.reserve_liquidity_mint.clone(),
+        accounts.reserve_liquidity_supply.clone(),
+        accounts.reserve_collateral_mint.clone(),
+        accounts.reserve_destination_deposit_collateral.clone(),
+        accounts.user_source_liquidity.clone(),
+        program.clone(),
+        accounts.token_program.clone(),
+        accounts.instruction_sysvar_account.clone(),
+        optional_info(&accounts.obligation_farm_user_state, program),
+        optional_info(&accounts.reserve_farm_state, program),
+        accounts.farms_program.clone(),
+    ];
+
+    invoke_kamino(
+        program,
+        metas,
+        &account_infos,
+        [DEPOSIT_RESERVE_LIQUIDITY_AND_OBLIGATION_COLLATERAL_V2_DISCRIMINATOR.as_ref(), &amount.to_le_bytes()].concat(),
+        signer_seeds,
+    )
+}
+
+/// # Borrow liquidity against the obligation collateral
+pub fn borrow_obligation_liquidity_v2(accounts: BorrowObligationLiquidityV2, amount: u64, signer_seeds: &[&[&[u8]]]) -> Result<()> {
+    let program = &accounts.kamino_lending_program;
+
+    let metas = vec![
+        AccountMeta::new(accounts.owner.key(), true),                                       // owner
+        AccountMeta::new(accounts.obligation.key(), false),                                 // obligation
+        AccountMeta::new_readonly(accounts.lending_market.key(), false),                    // lending_market
+        AccountMeta::new_readonly(accounts.lending_market_authority.key(), false),          // lending_market_authority
+        AccountMeta::new(accounts.borrow_reserve.key(), false),                             // borrow_reserve
+        AccountMeta::new_readonly(accounts.borrow_reserve_liquidity_mint.key(), false),     // borrow_reserve_liquidity_mint
+        AccountMeta::new(accounts.reserve_source_liquidity.key(), false),                   // reserve_source_liquidity
+        AccountMeta::new(accounts.borrow_reserve_liquidity_fee_receiver.key(), false),      // borrow_reserve_liquidity_fee_receiver
+        AccountMeta::new(accounts.user_destination_liquidity.key(), false),                 // user_destination_liquidity
+        optional_meta(&accounts.referrer_token_state, program, true),                       // [optional] referrer_token_state
+        AccountMeta::new_readonly(accounts.token_program.key(), false),                     // token_program
+        AccountMeta::new_readonly(accounts.instruction_sysvar_account.key(), false),        // instruction_sysvar_account
+        optional_meta(&accounts.obligation_farm_user_state, program, true),                 // [optional] obligation_farm_user_state
+        optional_meta(&accounts.reserve_farm_state, program, true),                         // [optional] reserve_farm_state
+        AccountMeta::new_readonly(accounts.farms_program.key(), false),                     // farms_program
+    ];
+
+    let account_infos = vec![
+        accounts.owner.clone(),
+        accounts.obligation.clone(),
+        accounts.lending_market.clone(),
+        accounts.lending_market_authority.clone(),
+        accounts.borrow_reserve.clone(),
+        accounts.borrow_reserve_liquidity_mint.clone(),
+        accounts.reserve_source_liquidity.clone(),
+        accounts.borrow_reserve_liquidity_fee_receiver.clone(),
+        accounts.user_destination_liquidity.clone(),
+        optional_info(&accounts.referrer_token_state, program),
+        accounts.token_program.clone(),
+        accounts.instruction_sysvar_account.clone(),
+        optional_info(&accounts.obligation_farm_user_state, program),
+        optional_info(&accounts.reserve_farm_state, program),
+        accounts.farms_program.clone(),
+    ];
+
+    invoke_kamino(
+        program,
+        metas,
+        &account_infos,
+        [BORROW_OBLIGATION_LIQUIDITY_V2_DISCRIMINATOR.as_ref(), &amount.to_le_bytes()].concat(),
+        signer_seeds,
+    )
+}
+
+/// # Withdraw obligation collateral as liquidity
+///
+/// `u64::MAX` withdraws as much collateral of the reserve as Kamino lets the obligation withdraw without
+/// breaking its LTV, which is all of it only once the obligation has no debt left.
+pub fn withdraw_obligation_collateral_and_redeem_reserve_collateral_v2(accounts: WithdrawObligationCollateralAndRedeemReserveCollateralV2, amount: u64, signer_seeds: &[&[&[u8]]]) -> Result<()> {
+    let program = &accounts.kamino_lending_program;
+
+    let metas = vec![
+        AccountMeta::new(accounts.owner.key(), true),                                       // owner
+        AccountMeta::new(accounts.obligation.key(), false),                                 // obligation
+        AccountMeta::new_readonly(accounts.lending_market.key(), false),                    // lending_market
+        AccountMeta::new_readonly(accounts.lending_market_authority.key(), false),          // lending_market_authority
+        AccountMeta::new(accounts.withdraw_reserve.key(), false),                           // withdraw_reserve
+        AccountMeta::new_readonly(accounts.reserve_liquidity_mint.key(), false),            // reserve_liquidity_mint
+        AccountMeta::new(accounts.reserve_source_collateral.key(), false),                  // reserve_source_collateral
+        AccountMeta::new(accounts.reserve_collateral_mint.key(), false),                    // reserve_collateral_mint
+        AccountMeta::new(accounts.reserve_liquidity_supply.key(), false),                   // reserve_liquidity_supply
+        AccountMeta::new(accounts.user_destination_liquidity.key(), false),                 // user_destination_liquidity
+        AccountMeta::new_readonly(program.key(), false),                                    // [optional] placeholder_user_destination_collateral
+        AccountMeta::new_readonly(accounts.token_program.key(), false),                     // collateral_token_program
+        AccountMeta::new_readonly(accounts.token_program.key(), false),                     // liquidity_token_program
+        AccountMeta::new_readonly(accounts.instruction_sysvar_account.key(), false),        // instruction_sysvar_account
+        optional_meta(&accounts.obligation_farm_user_state, program, true),                 // [optional] obligation_farm_user_state
+        optional_meta(&accounts.reserve_farm_state, program, true),                         // [optional] reserve_farm_state
+        AccountMeta::new_readonly(accounts.farms_program.key(), false),                     // farms_program
+    ];
+
+    let account_infos = vec![
+        accounts.owner.clone(),
+        accounts.obligation.clone(),
+        accounts.lending_market.clone(),
+        accounts.lending_market_authority.clone(),
+        accounts.withdraw_reserve.clone(),
+        accounts.reserve_liquidity_mint.clone(),
+        accounts.reserve_source_collateral.clone(),
+        accounts.reserve_collateral_mint.clone(),
+        accounts.reserve_liquidity_supply.clone(),
+        accounts.user_destination_liquidity.clone(),
+        program.clone(),
+        accounts.token_program.clone(),
+        accounts.instruction_sysvar_account.clone(),
+        optional_info(&accounts.obligation_farm_user_state, program),
+        optional_info(&accounts.reserve_farm_state, program),
+        accounts.farms_program.clone(),
+    ];
+
+    invoke_kamino(
+        program,
+        metas,
+        &account_infos,
+        [WITHDRAW_OBLIGATION_COLLATERAL_AND_REDEEM_RESERVE_COLLATERAL_V2_DISCRIMINATOR.as_ref(), &amount.to_le_bytes()].concat(),
+        signer_seeds,
+    )
+}
+
+/// # Repay obligation liquidity
+///
+/// `u64::MAX` repays the whole debt of the reserve, any other amount is capped to the debt.
+pub fn repay_obligation_liquidity_v2(accounts: RepayObligationLiquidityV2, amount: u64, signer_seeds: &[&[&[u8]]]) -> Result<()> {
+    let program = &accounts.kamino_lending_program;
+
+    let metas = vec![
+        AccountMeta::new(accounts.owner.key(), true),                                       // owner
+        AccountMeta::new(accounts.obligation.key(), false),                                 // obligation
+        AccountMeta::new_readonly(accounts.lending_market.key(), false),                    // lending_market
+        AccountMeta::new(accounts.repay_reserve.key(), false),                              // repay_reserve
+        AccountMeta::new_readonly(accounts.reserve_liquidity_mint.key(), false),            // reserve_liquidity_mint
+        AccountMeta::new(accounts.reserve_destination_liquidity.key(), false),              // reserve_destination_liquidity
+        AccountMeta::new(accounts.user_source_liquidity.key(), false),                      // user_source_liquidity
+        AccountMeta::new_readonly(accounts.token_program.key(), false),                     // token_program
+        AccountMeta::new_readonly(accounts.instruction_sysvar_account.key(), false),        // instruction_sysvar_account
+        optional_meta(&accounts.obligation_farm_user_state, program, true),                 // [optional] obligation_farm_user_state
+        optional_meta(&accounts.reserve_farm_state, program, true),                         // [optional] reserve_farm_state
+        AccountMeta::new_readonly(accounts.lending_market_authority.key(), false),          // lending_market_authority
+        AccountMeta::new_readonly(accounts.farms_program.key(), false),                     // farms_program
+    ];
+
+    let account_infos = vec![
+        accounts.owner.clone(),
+        accounts.obligation.clone(),
+        accounts.lending_market.clone(),
+        accounts.repay_reserve.clone(),
+        accounts.reserve_liquidity_mint.clone(),
+        accounts.reserve_destination_liquidity.clone(),
+        accounts.user_source_liquidity.clone(),
+        accounts.token_program.clone(),
+        accounts.instruction_sysvar_account.clone(),
+        optional_info(&accounts.obligation_farm_user_state, program),
+        optional_info(&accounts.reserve_farm_state, program),
+        accounts.lending_market_authority.clone(),
+        accounts.farms_program.clone(),
+    ];
+
+    invoke_kamino(
+        program,
+        metas,
+        &account_infos,
+        [REPAY_OBLIGATION_LIQUIDITY_V2_DISCRIMINATOR.as_ref(), &amount.to_le_bytes()].concat(),
+        signer_seeds,
+    )
+}
+
+/// # Set up the referrer token state
+///
+/// The account a reserve accrues the referral fees of one referrer in, one per reserve.
+pub fn init_referrer_token_state(accounts: InitReferrerTokenState) -> Result<()> {
+    let metas = vec![
+        AccountMeta::new(accounts.payer.key(), true),                               // payer
+        AccountMeta::new_readonly(accounts.lending_market.key(), false),            // lending_market
+        AccountMeta::new_readonly(accounts.reserve.key(), false),                   // reserve
+        AccountMeta::new_readonly(accounts.referrer.key(), false),                  // referrer
+        AccountMeta::new(accounts.referrer_token_state.key(), false),               // referrer_token_state
+        AccountMeta::new_readonly(accounts.rent.key(), false),                      // rent
+        AccountMeta::new_readonly(accounts.system_program.key(), false),            // system_program
+    ];
+
+    let account_infos = vec![
+        accounts.payer.clone(),
+        accounts.lending_market.clone(),
+        accounts.reserve.clone(),
+        accounts.referrer.clone(),
+        accounts.referrer_token_state.clone(),
+        accounts.rent.clone(),
+        accounts.system_program.clone(),
+    ];
+
+    invoke_kamino(
+        &accounts.kamino_lending_program,
+        metas,
+        &account_infos,
+        INIT_REFERRER_TOKEN_STATE_DISCRIMINATOR.to_vec(),
+        &[],
+    )
+}
+
+/// # Withdraw the referral fees
+///
+/// Everything the referrer token state accrued is paid into a token account owned by the referrer.
+pub fn withdraw_referrer_fees(accounts: WithdrawReferrerFees, signer_seeds: &[&[&[u8]]]) -> Result<()> {
+    let metas = vec![
+        AccountMeta::new(accounts.referrer.key(), true),                            // referrer
+        AccountMeta::new(accounts.referrer_token_state.key(), false),               // referrer_token_state
+        AccountMeta::new(accounts.reserve.key(), false),                            // reserve
+        AccountMeta::new_readonly(accounts.reserve_liquidity_mint.key(), false),    // reserve_liquidity_mint
+        AccountMeta::new(accounts.reserve_supply_liquidity.key(), false),           // reserve_supply_liquidity
+        AccountMeta::new(accounts.referrer_token_account.key(), false),             // referrer_token_account
+        AccountMeta::new_readonly(accounts.lending_market.key(), false),            // lending_market
+        AccountMeta::new_readonly(accounts.lending_market_authority.key(), false),  // lending_market_authority
+        AccountMeta::new_readonly(accounts.token_program.key(), false),             // token_program
+    ];
+
+    let account_infos = vec![
+        accounts.referrer.clone(),
+        accounts.referrer_token_state.clone(),
+        accounts.reserve.clone(),
+        accounts.reserve_liquidity_mint.clone(),
+        accounts.reserve_supply_liquidity.clone(),
+        accounts.referrer_token_account.clone(),
+        accounts.lending_market.clone(),
+        accounts.lending_market_authority.clone(),
+        accounts.token_program.clone(),
+    ];
+
+    invoke_kamino(
+        &accounts.kamino_lending_program,
+        metas,
+        &account_infos,
+        WITHDRAW_REFERRER_FEES_DISCRIMINATOR.to_vec(),
+        signer_seeds,
+    )
+}
+
+/// # Harvest a farm reward
+///
+/// Claims what the farm user state accrued for one reward index of the farm into a token account
+/// of the owner. Obligation farm user states are delegated to the obligation owner, so it signs.
+pub fn harvest_reward(accounts: HarvestReward, reward_index: u64, signer_seeds: &[&[&[u8]]]) -> Result<()> {
+    let program = &accounts.farms_program;
+
+    let metas = vec![
+        AccountMeta::new(accounts.owner.key(), true),                               // owner
+        AccountMeta::new(accounts.user_state.key(), false),                         // user_state
+        AccountMeta::new(accounts.farm_state.key(), false),                         // farm_state
+        AccountMeta::new_readonly(accounts.global_config.key(), false),             // global_config
+        AccountMeta::new_readonly(accounts.reward_mint.key(), false),               // reward_mint
+        AccountMeta::new(accounts.user_reward_ata.key(), false),                    // user_reward_ata
+        AccountMeta::new(accounts.rewards_vault.key(), false),                      // rewards_vault
+        AccountMeta::new(accounts.rewards_treasury_vault.key(), false),             // rewards_treasury_vault
+        AccountMeta::new_readonly(accounts.farm_vaults_authority.key(), false),     // farm_vaults_authority
+        optional_meta(&accounts.scope_prices, program, false),                      // [optional] scope_prices
+        AccountMeta::new_readonly(accounts.token_program.key(), false),             // token_program
+    ];
+
+    let account_infos = vec![
+        accounts.owner.clone(),
+        accounts.user_state.clone(),
+        accounts.farm_state.clone(),
+        accounts.global_config.clone(),
+        accounts.reward_mint.clone(),
+        accounts.user_reward_ata.clone(),
+        accounts.rewards_vault.clone(),
+        accounts.rewards_treasury_vault.clone(),
+        accounts.farm_vaults_authority.clone(),
+        optional_info(&accounts.scope_prices, program),
+        accounts.token_program.clone(),
+    ];
+
+    invoke_kamino(
+        program,
+        metas,
+        &account_infos,
+        [HARVEST_REWARD_DISCRIMINATOR.as_ref(), &reward_index.to_le_bytes()].concat(),
+        signer_seeds,
+    )
+}
+
+/// Kamino expects its own program id in place of an optional account that is not passed.
+fn optional_meta(account: &Option<AccountInfo>, program: &AccountInfo, is_writable: bool) -> AccountMeta {
+    match account {
+        Some(account) if is_writable => AccountMeta::new(account.key(), false),
+        Some(account) => AccountMeta::new_readonly(account.key(), false),
+        None => AccountMeta::new_readonly(program.key(), false),
+    }
+}
+
+fn optional_info<'info>(account: &Option<AccountInfo<'info>>, program: &AccountInfo<'info>) -> AccountInfo<'info> {
+    account.clone().unwrap_or_else(|| program.clone())
+}
+
+fn invoke_kamino(
+    program: &AccountInfo,
+    metas: Vec<AccountMeta>,
+    account_infos: &[AccountInfo],
+    data: Vec<u8>,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    let ix = Instruction {
+        program_id: program.key(),
+        accounts: metas,
+        data,
+    };
+
+    invoke_signed(&ix, account_infos, signer_seeds)?;
+
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use std::cell::Ref;
+use anchor_lang::prelude::*;
+use crate::constant::KAMINO_FARMS_PROGRAM_ID;
+
+pub const FARM_STATE_DISCRIMINATOR: [u8; 8] = [198, 102, 216, 74, 63, 66, 163, 190];
+
+/// Offsets in the Kamino Farms `FarmState` account (discriminator included)
+const REWARD_INFOS_OFFSET: usize = 192;
+const NUM_REWARD_TOKENS_OFFSET: usize = 7312;
+
+/// Offsets inside a `RewardInfo` entry
+const REWARD_INFOS_LEN: usize = 10;
+const REWARD_INFO_SIZE: usize = 712;
+const REWARD_MINT_OFFSET: usize = 0;
+const REWARDS_VAULT_OFFSET: usize = 120;
+
+/// A reward of the farm
+pub struct RewardInfo {
+    pub reward_mint: Pubkey,
+    pub rewards_vault: Pubkey,
+}
+
+/// # Farm state view
+///
+/// Read-only view over the data of a Kamino Farms farm state, like the `Obligation` view it has to be
+/// dropped before the farm state is handed to a Kamino Farms CPI.
+pub struct FarmState<'a> {
+    data: Ref<'a, &'a mut [u8]>,
+}
+
+impl<'a> FarmState<'a> {
+    pub fn load(farm_state: &'a AccountInfo) -> Result<Self> {
+        require_keys_eq!(*farm_state.owner, KAMINO_FARMS_PROGRAM_ID, ErrorCode::AccountOwnedByWrongProgram);
+
+        let data = farm_state.try_borrow_data()?;
+        require_gte!(data.len(), NUM_REWARD_TOKENS_OFFSET + 8, ErrorCode::AccountDidNotDeserialize);
+        require!(data[..8] == FARM_STATE_DISCRIMINATOR, ErrorCode::AccountDiscriminatorMismatch);
+
+        Ok(Self { data })
+    }
+
+    fn pubkey(&self, offset: usize) -> Pubkey {
+        Pubkey::new_from_array(self.data[offset..offset + 32].try_into().unwrap())
+    }
+
+    /// Initialized rewards, in reward index order.
+    pub fn rewards(&self) -> impl Iterator<Item = RewardInfo> + '_ {
+        let num_reward_tokens = u64::from_le_bytes(self.data[NUM_REWARD_TOKENS_OFFSET..NUM_REWARD_TOKENS_OFFSET + 8].try_into().unwrap());
+
+        (0..REWARD_INFOS_LEN.min(num_reward_tokens as usize))
+            .map(|i| REWARD_INFOS_OFFSET + i * REWARD_INFO_SIZE)
+            .map(|offset| RewardInfo {
+                reward_mint: self.pubkey(offset + REWARD_MINT_OFFSET),
+                rewards_vault: self.pubkey(offset + REWARDS_VAULT_OFFSET),
+            })
+    }
+}
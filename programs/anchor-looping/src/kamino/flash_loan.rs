@@ -0,0 +1,50 @@
+use anchor_lang::{prelude::*, solana_program::sysvar::instructions::{load_current_index_checked, load_instruction_at_checked}};
+use crate::{constant::KAMINO_PROGRAM_ID, error::LoopingError};
+use super::{FLASH_BORROW_RESERVE_LIQUIDITY_DISCRIMINATOR, FLASH_REPAY_RESERVE_LIQUIDITY_DISCRIMINATOR};
+
+/// Position of the reserve and of the user token account in both flash loan instructions: user transfer
+/// authority, lending market authority, lending market, reserve, reserve liquidity mint, reserve liquidity,
+/// user liquidity, then the fee receiver, the optional referrer accounts, the sysvar and the token program
+const FLASH_LOAN_RESERVE_INDEX: usize = 3;
+const FLASH_LOAN_USER_LIQUIDITY_INDEX: usize = 6;
+
+/// # Check the flash loan around the current instruction
+/// 
+/// Kamino refuses flash loans issued through CPI, so the client has to sandwich our instruction 
+/// between a top-level `flash_borrow_reserve_liquidity` and `flash_repay_reserve_liquidity`. 
+/// 
+/// We make sure that the previous instruction borrows at least `amount` from `reserve` into `user_liquidity` 
+/// and that the next one repays that exact borrow from the same token account, so the liquidity we 
+/// use is the flash loan and not something the signer had lying around. The flash loan can be larger
+/// than what the instruction uses when `amount` is only known on-chain, the rest just goes back.
+/// 
+/// Returns the flash loan amount.
+pub fn check_flash_loan(instruction_sysvar_account: &AccountInfo, reserve: &Pubkey, user_liquidity: &Pubkey, amount: u64) -> Result<u64> {
+    let current_index = load_current_index_checked(instruction_sysvar_account)? as usize;
+    require!(current_index > 0, LoopingError::InvalidFlashLoan);
+
+    let borrow_ix = load_instruction_at_checked(current_index - 1, instruction_sysvar_account)
+        .map_err(|_| LoopingError::InvalidFlashLoan)?;
+    let repay_ix = load_instruction_at_checked(current_index + 1, instruction_sysvar_account)
+        .map_err(|_| LoopingError::InvalidFlashLoan)?;
+
+    // Flash borrow: discriminator + liquidity_amount
+    require_keys_eq!(borrow_ix.program_id, KAMINO_PROGRAM_ID, LoopingError::InvalidFlashLoan);
+    require!(borrow_ix.data.len() == 16 && borrow_ix.data.starts_with(&FLASH_BORROW_RESERVE_LIQUIDITY_DISCRIMINATOR), LoopingError::InvalidFlashLoan);
+    let flash_amount = u64::from_le_bytes(borrow_ix.data[8..16].try_into().unwrap());
+    require_gte!(flash_amount, amount, LoopingError::InvalidFlashLoan);
+    require!(borrow_ix.accounts.len() > FLASH_LOAN_USER_LIQUIDITY_INDEX, LoopingError::InvalidFlashLoan);
+    require_keys_eq!(borrow_ix.accounts[FLASH_LOAN_RESERVE_INDEX].pubkey, *reserve, LoopingError::InvalidFlashLoan);
+    require_keys_eq!(borrow_ix.accounts[FLASH_LOAN_USER_LIQUIDITY_INDEX].pubkey, *user_liquidity, LoopingError::InvalidFlashLoan);
+
+    // Flash repay: discriminator + liquidity_amount + borrow_instruction_index
+    require_keys_eq!(repay_ix.program_id, KAMINO_PROGRAM_ID, LoopingError::InvalidFlashLoan);
+    require!(repay_ix.data.len() == 17 && repay_ix.data.starts_with(&FLASH_REPAY_RESERVE_LIQUIDITY_DISCRIMINATOR), LoopingError::InvalidFlashLoan);
+    require_eq!(u64::from_le_bytes(repay_ix.data[8..16].try_into().unwrap()), flash_amount, LoopingError::InvalidFlashLoan);
+    require_eq!(repay_ix.data[16] as usize, current_index - 1, LoopingError::InvalidFlashLoan);
+    require!(repay_ix.accounts.len() > FLASH_LOAN_USER_LIQUIDITY_INDEX, LoopingError::InvalidFlashLoan);
+    require_keys_eq!(repay_ix.accounts[FLASH_LOAN_RESERVE_INDEX].pubkey, *reserve, LoopingError::InvalidFlashLoan);
+    require_keys_eq!(repay_ix.accounts[FLASH_LOAN_USER_LIQUIDITY_INDEX].pubkey, *user_liquidity, LoopingError::InvalidFlashLoan);
+
+    Ok(flash_amount)
+}
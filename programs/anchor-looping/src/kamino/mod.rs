@@ -0,0 +1,12 @@
+pub mod cpi;
+pub use cpi::*;
+pub mod farm;
+pub use farm::*;
+pub mod flash_loan;
+pub use flash_loan::*;
+pub mod obligation;
+pub use obligation::*;
+pub mod refresh;
+pub use refresh::*;
+pub mod reserve;
+pub use reserve::*;
@@ -0,0 +1,244 @@
+use std::cell::Ref;
+use anchor_lang::prelude::*;
+use crate::{constant::KAMINO_PROGRAM_ID, error::LoopingError};
+
+pub const OBLIGATION_DISCRIMINATOR: [u8; 8] = [168, 206, 141, 106, 88, 76, 172, 167];
+
+/// Offsets in the Kamino `Obligation` account (discriminator included)
+const DEPOSITS_OFFSET: usize = 96;
+const DEPOSITED_VALUE_SF_OFFSET: usize = 1192;
+const BORROWS_OFFSET: usize = 1208;
+const BORROW_FACTOR_ADJUSTED_DEBT_VALUE_SF_OFFSET: usize = 2208;
+const BORROWED_ASSETS_MARKET_VALUE_SF_OFFSET: usize = 2224;
+const ALLOWED_BORROW_VALUE_SF_OFFSET: usize = 2240;
+const HAS_DEBT_OFFSET: usize = 2287;
+
+/// Offsets inside an `ObligationCollateral` entry
+const DEPOSITS_LEN: usize = 8;
+const DEPOSIT_SIZE: usize = 136;
+const DEPOSITED_AMOUNT_OFFSET: usize = 32;
+
+/// Offsets inside an `ObligationLiquidity` entry
+const BORROWS_LEN: usize = 5;
+const BORROW_SIZE: usize = 200;
+const BORROWED_AMOUNT_SF_OFFSET: usize = 88;
+
+/// A collateral slot of the obligation
+pub struct ObligationCollateral {
+    pub deposit_reserve: Pubkey,
+    pub deposited_amount: u64,
+}
+
+/// A borrow slot of the obligation
+pub struct ObligationLiquidity {
+    pub borrow_reserve: Pubkey,
+    pub borrowed_amount_sf: u128,
+}
+
+/// # Obligation view
+/// 
+/// Read-only view over the data of a Kamino obligation. Nothing is deserialized up front, every
+/// getter reads its field straight from the borrowed account data, so the view has to be dropped
+/// before the obligation is handed to a Kamino CPI.
+/// 
+/// Note: The values are the ones of the last `refresh_obligation`, refresh it first if they matter.
+pub struct Obligation<'a> {
+    data: Ref<'a, &'a mut [u8]>,
+}
+
+impl<'a> Obligation<'a> {
+    pub fn load(obligation: &'a AccountInfo) -> Result<Self> {
+        require_keys_eq!(*obligation.owner, KAMINO_PROGRAM_ID, ErrorCode::AccountOwnedByWrongProgram);
+
+        let data = obligation.try_borrow_data()?;
+        require_gte!(data.len(), HAS_DEBT_OFFSET + 1, ErrorCode::AccountDidNotDeserialize);
+        require!(data[..8] == OBLIGATION_DISCRIMINATOR, ErrorCode::AccountDiscriminatorMismatch);
+
+        Ok(Self { data })
+    }
+
+    fn pubkey(&self, offset: usize) -> Pubkey {
+        Pubkey::new_from_array(self.data[offset..offset + 32].try_into().unwrap())
+    }
+
+    fn u64(&self, offset: usize) -> u64 {
+        u64::from_le_bytes(self.data[offset..offset + 8].try_into().unwrap())
+    }
+
+    fn u128(&self, offset: usize) -> u128 {
+        u128::from_le_bytes(self.data[offset..offset + 16].try_into().unwrap())
+    }
+
+    /// Collateral slots in use, in the order Kamino stores them.
+    pub fn deposits(&self) -> impl Iterator<Item = ObligationCollateral> + '_ {
+        (0..DEPOSITS_LEN)
+            .map(|i| DEPOSITS_OFFSET + i * DEPOSIT_SIZE)
+            .map(|offset| ObligationCollateral {
+                deposit_reserve: self.pubkey(offset),
+                deposited_amount: self.u64(offset + DEPOSITED_AMOUNT_OFFSET),
+            })
+            .filter(|deposit| deposit.deposit_reserve != Pubkey::default())
+    }
+
+    /// Borrow slots in use, in the order Kamino stores them.
+    pub fn borrows(&self) -> impl Iterator<Item = ObligationLiquidity> + '_ {
+        (0..BORROWS_LEN)
+            .map(|i| BORROWS_OFFSET + i * BORROW_SIZE)
+            .map(|offset| ObligationLiquidity {
+                borrow_reserve: self.pubkey(offset),
+                borrowed_amount_sf: self.u128(offset + BORROWED_AMOUNT_SF_OFFSET),
+            })
+            .filter(|borrow| borrow.borrow_reserve != Pubkey::default())
+    }
+
+    pub fn deposited_value_sf(&self) -> u128 {
+        self.u128(DEPOSITED_VALUE_SF_OFFSET)
+    }
+
+    pub fn borrow_factor_adjusted_debt_value_sf(&self) -> u128 {
+        self.u128(BORROW_FACTOR_ADJUSTED_DEBT_VALUE_SF_OFFSET)
+    }
+
+    pub fn borrowed_assets_market_value_sf(&self) -> u128 {
+        self.u128(BORROWED_ASSETS_MARKET_VALUE_SF_OFFSET)
+    }
+
+    pub fn allowed_borrow_value_sf(&self) -> u128 {
+        self.u128(ALLOWED_BORROW_VALUE_SF_OFFSET)
+    }
+
+    /// # LTV of the obligation
+    /// 
+    /// The borrow factor adjusted debt over the value the collateral allows to borrow, in bps. At 10_000 
+    /// Kamino stops any further borrow, liquidation comes a bit later at the unhealthy borrow value.
+    pub fn ltv_bps(&self) -> Result<u64> {
+        let debt_value_sf = self.borrow_factor_adjusted_debt_value_sf();
+        let allowed_borrow_value_sf = self.allowed_borrow_value_sf();
+        if debt_value_sf == 0 {
+            return Ok(0);
+        }
+        if allowed_borrow_value_sf == 0 {
+            return Ok(u64::MAX);
+        }
+
+        let ltv_bps = debt_value_sf.checked_mul(10_000).ok_or(error!(LoopingError::MathOverflow))? / allowed_borrow_value_sf;
+
+        Ok(ltv_bps.min(u64::MAX as u128) as u64)
+    }
+
+    /// # Leverage of the obligation
+    /// 
+    /// The deposited value over the equity (deposited minus borrowed value), in bps. A position without
+    /// debt sits at 1x (`10_000`), one without equity left reads as `u64::MAX`.
+    pub fn leverage_bps(&self) -> Result<u64> {
+        let deposited_value_sf = self.deposited_value_sf();
+        let borrowed_value_sf = self.borrowed_assets_market_value_sf();
+        if borrowed_value_sf == 0 {
+            return Ok(10_000);
+        }
+        if deposited_value_sf <= borrowed_value_sf {
+            return Ok(u64::MAX);
+        }
+
+        let leverage_bps = deposited_value_sf.checked_mul(10_000).ok_or(error!(LoopingError::MathOverflow))? / (deposited_value_sf - borrowed_value_sf);
+
+        Ok(leverage_bps.min(u64::MAX as u128) as u64)
+    }
+
+    /// # Debt value to reach a leverage
+    /// 
+    /// The debt value to add, all of it swapped into collateral, for the leverage to reach `target_leverage_bps`.
+    /// Each unit borrowed and deposited back adds as much deposit as debt, so the equity doesn't move and the
+    /// deposit has to grow to the target times the equity.
+    pub fn borrow_value_for_leverage_sf(&self, target_leverage_bps: u32) -> Result<u128> {
+        let deposited_value_sf = self.deposited_value_sf();
+        let borrowed_value_sf = self.borrowed_assets_market_value_sf();
+        require_gt!(deposited_value_sf, borrowed_value_sf, LoopingError::LeverageTooHigh);
+
+        let target_deposited_value_sf = (deposited_value_sf - borrowed_value_sf)
+            .checked_mul(target_leverage_bps as u128)
+            .ok_or(error!(LoopingError::MathOverflow))? / 10_000;
+        require_gt!(target_deposited_value_sf, deposited_value_sf, LoopingError::LeverageTargetReached);
+
+        Ok(target_deposited_value_sf - deposited_value_sf)
+    }
+
+    /// Every reserve the obligation uses: the deposit reserves followed by the borrow reserves, in the
+    /// order of the obligation slots. This is the order Kamino's `refresh_obligation` wants them in.
+    pub fn reserves(&self) -> impl Iterator<Item = Pubkey> + '_ {
+        self.deposits()
+            .map(|deposit| deposit.deposit_reserve)
+            .chain(self.borrows().map(|borrow| borrow.borrow_reserve))
+    }
+
+    /// # Reserves to refresh the obligation with
+    /// 
+    /// Picks each of the obligation `reserves()` from `reserves`.
+    pub fn refresh_reserves<'info>(&self, reserves: &[AccountInfo<'info>]) -> Result<Vec<AccountInfo<'info>>> {
+        self.reserves()
+            .map(|key| {
+                reserves
+                    .iter()
+                    .find(|reserve| reserve.key() == key)
+                    .cloned()
+                    .ok_or(error!(LoopingError::ObligationReserveMismatch))
+            })
+            .collect()
+    }
+}
+
+/// # Split the obligation reserves off the remaining accounts
+/// 
+/// The remaining accounts start with every reserve the obligation uses, in the order of `reserves()`,
+/// and carry on with whatever else the instruction needs (the Jupiter route accounts). The obligation 
+/// is read before anything touches it, so the list is the one of the obligation going in.
+pub fn split_obligation_reserves<'a, 'info>(obligation: &AccountInfo<'info>, remaining_accounts: &'a [AccountInfo<'info>]) -> Result<(&'a [AccountInfo<'info>], &'a [AccountInfo<'info>])> {
+    let obligation = Obligation::load(obligation)?;
+    let keys: Vec<Pubkey> = obligation.reserves().collect();
+    require_gte!(remaining_accounts.len(), keys.len(), LoopingError::InsufficientRemainingAccounts);
+
+    let (reserves, rest) = remaining_accounts.split_at(keys.len());
+    for (reserve, key) in reserves.iter().zip(keys) {
+        require_keys_eq!(reserve.key(), key, LoopingError::ObligationReserveMismatch);
+    }
+
+    Ok((reserves, rest))
+}
+
+/// # Check the position health
+/// 
+/// Run on a freshly refreshed obligation at the end of an instruction. The LTV can't be left above
+/// `max_ltv_bps`, unless the instruction brought it down: a position already over the limit (prices
+/// moved or the limit was lowered) can still be unwound.
+pub fn check_health(obligation: &AccountInfo, ltv_before_bps: u64, max_ltv_bps: u16) -> Result<u64> {
+    let ltv_bps = Obligation::load(obligation)?.ltv_bps()?;
+    require!(ltv_bps <= max_ltv_bps as u64 || ltv_bps < ltv_before_bps, LoopingError::HealthFactorTooLow);
+
+    Ok(ltv_bps)
+}
+
+/// # Check the position is unwound
+/// 
+/// Run by `close_position` before the position account goes away: the obligation can't keep any debt
+/// nor any collateral in `reserve_collateral`, or they would be left behind without a position.
+pub fn check_unwound(obligation: &AccountInfo, reserve_collateral: &Pubkey) -> Result<()> {
+    let obligation = Obligation::load(obligation)?;
+    require!(obligation.borrows().all(|borrow| borrow.borrowed_amount_sf == 0), LoopingError::PositionNotUnwound);
+    require!(
+        obligation.deposits().all(|deposit| deposit.deposit_reserve != *reserve_collateral || deposit.deposited_amount == 0),
+        LoopingError::PositionNotUnwound
+    );
+
+    Ok(())
+}
+
+/// # Check the position leverage
+/// 
+/// Run next to `check_health` by the instructions that can raise the leverage (`looping`, `leverage` 
+/// and `withdraw`). The leverage can't be left above the `max_leverage_bps` of the reserve pair.
+pub fn check_leverage(obligation: &AccountInfo, max_leverage_bps: u32) -> Result<()> {
+    let leverage_bps = Obligation::load(obligation)?.leverage_bps()?;
+    require_gte!(max_leverage_bps as u64, leverage_bps, LoopingError::LeverageTooHigh);
+
+    Ok(())
+}
@@ -0,0 +1,52 @@
+use anchor_lang::prelude::*;
+use super::{refresh_obligation, refresh_reserve, Obligation, RefreshObligation, RefreshReserve};
+
+/// Accounts to refresh a position with
+pub struct RefreshPosition<'a, 'info> {
+    pub obligation: AccountInfo<'info>,
+    pub lending_market: AccountInfo<'info>,
+    pub reserve_collateral: AccountInfo<'info>,
+    /// Only needed once the obligation has a borrow
+    pub reserve_borrow: Option<AccountInfo<'info>>,
+    pub scope_oracle: AccountInfo<'info>,
+    pub kamino_lending_program: AccountInfo<'info>,
+    /// Every reserve the obligation uses, as split off the remaining accounts by `split_obligation_reserves`
+    pub reserves: &'a [AccountInfo<'info>],
+}
+
+/// # Refresh the position
+/// 
+/// Kamino wants every reserve the obligation uses refreshed in the same slot before the obligation,
+/// and the obligation refreshed before anything touches it. We refresh the named reserves, then
+/// any other reserve of the obligation, then the obligation with its reserves in the order it lists
+/// them (deposits first, borrows second).
+/// 
+/// We are going to only use scope as oracle (this is Kamino in-house oracle that doesn't require any crank
+/// since they do it for you)
+pub fn refresh_position(accounts: RefreshPosition) -> Result<()> {
+    // The named reserves first, then the ones only the obligation knows about
+    let mut named = vec![accounts.reserve_collateral.clone()];
+    named.extend(accounts.reserve_borrow.iter().cloned());
+    let others = accounts.reserves.iter().filter(|reserve| named.iter().all(|named| named.key() != reserve.key()));
+    for reserve in named.iter().chain(others) {
+        refresh_reserve(RefreshReserve {
+            reserve: reserve.clone(),
+            lending_market: accounts.lending_market.clone(),
+            scope_oracle: accounts.scope_oracle.clone(),
+            kamino_lending_program: accounts.kamino_lending_program.clone(),
+        })?;
+    }
+
+    let mut candidates = named;
+    candidates.extend_from_slice(accounts.reserves);
+    let reserves = Obligation::load(&accounts.obligation)?.refresh_reserves(&candidates)?;
+
+    refresh_obligation(
+        RefreshObligation {
+            lending_market: accounts.lending_market.clone(),
+            obligation: accounts.obligation.clone(),
+            kamino_lending_program: accounts.kamino_lending_program.clone(),
+        },
+        &reserves,
+    )
+}
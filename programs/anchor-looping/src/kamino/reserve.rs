@@ -0,0 +1,157 @@
+use anchor_lang::prelude::*;
+use crate::{constant::KAMINO_PROGRAM_ID, error::LoopingError};
+
+/// Offsets in the Kamino `Reserve` account (discriminator included)
+const LAST_UPDATE_SLOT_OFFSET: usize = 16;
+const LENDING_MARKET_OFFSET: usize = 32;
+const LIQUIDITY_MINT_OFFSET: usize = 128;
+const LIQUIDITY_MARKET_PRICE_SF_OFFSET: usize = 248;
+const LIQUIDITY_MINT_DECIMALS_OFFSET: usize = 272;
+
+/// # Reserve price
+/// 
+/// The few fields of a Kamino reserve we need to price a swap. We only read them at fixed
+/// offsets instead of pulling in the whole `Reserve` layout.
+pub struct ReservePrice {
+    pub last_update_slot: u64,
+    pub market_price_sf: u128,
+    pub mint_decimals: u8,
+}
+
+impl ReservePrice {
+    pub fn load(reserve: &AccountInfo) -> Result<Self> {
+        require_keys_eq!(*reserve.owner, KAMINO_PROGRAM_ID, ErrorCode::AccountOwnedByWrongProgram);
+
+        let data = reserve.try_borrow_data()?;
+        require_gte!(data.len(), LIQUIDITY_MINT_DECIMALS_OFFSET + size_of::<u64>(), ErrorCode::AccountDidNotDeserialize);
+
+        Ok(Self {
+            last_update_slot: u64::from_le_bytes(data[LAST_UPDATE_SLOT_OFFSET..LAST_UPDATE_SLOT_OFFSET + 8].try_into().unwrap()),
+            market_price_sf: u128::from_le_bytes(data[LIQUIDITY_MARKET_PRICE_SF_OFFSET..LIQUIDITY_MARKET_PRICE_SF_OFFSET + 16].try_into().unwrap()),
+            mint_decimals: data[LIQUIDITY_MINT_DECIMALS_OFFSET],
+        })
+    }
+
+    /// Market value of `amount` base units, as a scaled fraction.
+    pub fn value_sf(&self, amount: u64) -> Result<u128> {
+        let unit_price_sf = self.market_price_sf / 10u128.pow(self.mint_decimals as u32);
+
+        (amount as u128).checked_mul(unit_price_sf).ok_or(error!(LoopingError::MathOverflow))
+    }
+
+    /// Base units worth `value_sf`, rounded down. The inverse of `value_sf`.
+    pub fn amount(&self, value_sf: u128) -> Result<u64> {
+        let unit_price_sf = self.market_price_sf / 10u128.pow(self.mint_decimals as u32);
+        let amount = value_sf.checked_div(unit_price_sf).ok_or(error!(LoopingError::MathOverflow))?;
+
+        u64::try_from(amount).map_err(|_| error!(LoopingError::MathOverflow))
+    }
+}
+
+/// # Check the swap price against the oracle
+/// 
+/// Both reserves have to be refreshed in the current slot, so the market price is the one Kamino
+/// just pulled from the oracle. What the swap `received` can't be worth less than what it `spent` 
+/// by more than `max_deviation_bps`.
+pub fn check_swap_price(input_reserve: &AccountInfo, output_reserve: &AccountInfo, spent: u64, received: u64, max_deviation_bps: u16) -> Result<()> {
+    let slot = Clock::get()?.slot;
+    let input = ReservePrice::load(input_reserve)?;
+    let output = ReservePrice::load(output_reserve)?;
+    require_eq!(input.last_update_slot, slot, LoopingError::StaleReservePrice);
+    require_eq!(output.last_update_slot, slot, LoopingError::StaleReservePrice);
+
+    check_price_deviation(&input, &output, spent, received, max_deviation_bps)
+}
+
+/// The price half of `check_swap_price`, on reserve prices already loaded.
+fn check_price_deviation(input: &ReservePrice, output: &ReservePrice, spent: u64, received: u64, max_deviation_bps: u16) -> Result<()> {
+    let spent_value_sf = input.value_sf(spent)?;
+    let received_value_sf = output.value_sf(received)?;
+
+    // received / spent >= 1 - max_deviation
+    let lhs = received_value_sf.checked_mul(10_000).ok_or(error!(LoopingError::MathOverflow))?;
+    let rhs = spent_value_sf.checked_mul(10_000 - max_deviation_bps.min(10_000) as u128).ok_or(error!(LoopingError::MathOverflow))?;
+    require_gte!(lhs, rhs, LoopingError::PriceDeviationTooHigh);
+
+    Ok(())
+}
+
+/// # Check a reserve against the market config
+/// 
+/// The reserve has to belong to `lending_market` and lend `mint`, so a listed pair can't be opened
+/// with a look-alike reserve or mint account.
+pub fn check_reserve(reserve: &AccountInfo, lending_market: &Pubkey, mint: &Pubkey) -> Result<()> {
+    require_keys_eq!(*reserve.owner, KAMINO_PROGRAM_ID, ErrorCode::AccountOwnedByWrongProgram);
+
+    let data = reserve.try_borrow_data()?;
+    require_gte!(data.len(), LIQUIDITY_MINT_OFFSET + 32, ErrorCode::AccountDidNotDeserialize);
+
+    let reserve_lending_market = Pubkey::new_from_array(data[LENDING_MARKET_OFFSET..LENDING_MARKET_OFFSET + 32].try_into().unwrap());
+    let reserve_mint = Pubkey::new_from_array(data[LIQUIDITY_MINT_OFFSET..LIQUIDITY_MINT_OFFSET + 32].try_into().unwrap());
+    require_keys_eq!(reserve_lending_market, *lending_market, LoopingError::MarketNotAllowed);
+    require_keys_eq!(reserve_mint, *mint, LoopingError::MarketNotAllowed);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Kamino scaled fractions carry 60 fractional bits
+    const ONE_SF: u128 = 1 << 60;
+
+    fn price(usd: u128, mint_decimals: u8) -> ReservePrice {
+        ReservePrice { last_update_slot: 0, market_price_sf: usd * ONE_SF, mint_decimals }
+    }
+
+    #[test]
+    fn value_sf_scales_by_the_mint_decimals() {
+        let usdc = price(1, 6);
+
+        // 1 USDC is worth 1 USD, give or take the rounding of the unit price
+        let value_sf = usdc.value_sf(1_000_000).unwrap();
+        assert!(value_sf <= ONE_SF && ONE_SF - value_sf < 1_000_000);
+        assert_eq!(usdc.amount(value_sf).unwrap(), 1_000_000);
+    }
+
+    #[test]
+    fn amount_rejects_a_zero_price() {
+        assert_eq!(price(0, 6).amount(ONE_SF).unwrap_err(), error!(LoopingError::MathOverflow));
+    }
+
+    #[test]
+    fn price_deviation_within_the_bound() {
+        let usdc = price(1, 6);
+        let btc = price(100_000, 8);
+
+        // 1_000 USDC for 0.01 BTC is right on the oracle price
+        assert!(check_price_deviation(&usdc, &btc, 1_000_000_000, 1_000_000, 0).is_ok());
+        // 1% short of it, with 1% allowed
+        assert!(check_price_deviation(&usdc, &btc, 1_000_000_000, 990_000, 100).is_ok());
+        // Getting more than the oracle price is always fine
+        assert!(check_price_deviation(&usdc, &btc, 1_000_000_000, 1_100_000, 0).is_ok());
+    }
+
+    #[test]
+    fn price_deviation_above_the_bound() {
+        let usdc = price(1, 6);
+        let btc = price(100_000, 8);
+
+        assert_eq!(
+            check_price_deviation(&usdc, &btc, 1_000_000_000, 989_999, 100).unwrap_err(),
+            error!(LoopingError::PriceDeviationTooHigh)
+        );
+        assert_eq!(
+            check_price_deviation(&btc, &usdc, 1_000_000, 999_999_999, 0).unwrap_err(),
+            error!(LoopingError::PriceDeviationTooHigh)
+        );
+    }
+
+    #[test]
+    fn price_deviation_bound_is_capped_at_100_percent() {
+        let usdc = price(1, 6);
+
+        assert!(check_price_deviation(&usdc, &usdc, 1_000_000, 0, u16::MAX).is_ok());
+    }
+}
@@ -1,9 +1,23 @@
+// The only deprecated call in the crate is the `AccountInfo::realloc` of the IDL resize instruction that
+// `#[program]` (Anchor 0.31) generates at the crate root, next to the program module. No account of the
+// program uses `realloc`, so there is no narrower item the allow could sit on.
+#![allow(deprecated)]
+
 use anchor_lang::prelude::*;
 
 mod constant;
-use crate::constant::{FLAG_HAS_BORROWS, FLAG_HAS_COLLATERAL};
+mod error;
+use error::LoopingError;
+mod events;
+use events::*;
 mod instructions;
 use instructions::*;
+mod jupiter;
+use jupiter::resize_exact_in_route;
+mod kamino;
+use kamino::{check_health, check_leverage, check_unwound, split_obligation_reserves, Obligation};
+mod state;
+use state::{Config, PauseFlags, ReservePair};
 
 declare_id!("HZ4pzn7pTpkVRpxpszbvBxxQSS11Pu3oYt2PyWW6iFKU");
 
@@ -12,51 +26,445 @@ pub mod anchor_looping {
     use super::*;
 
     pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
-        ctx.accounts.initialize_user_metadata()?;
-        ctx.accounts.initialize_obligation()?;
-        ctx.accounts.initialize_obligation_farms_for_reserve()
-    }
+        ctx.accounts.check_reserve_pair()?;
+        ctx.accounts.initialize_position(&ctx.bumps)?;
 
-    pub fn deposit(ctx: Context<Deposit>, has_collateral_or_borrows_flags: u8, amount: u64) -> Result<()> {
-        ctx.accounts.refresh_reserve_collateral()?;
-        if has_collateral_or_borrows_flags & FLAG_HAS_COLLATERAL != 0 {
-            ctx.accounts.refresh_reserve_borrow()?;
+        // The Kamino accounts outlive a closed position, so reopening one skips them
+        if ctx.accounts.user_metadata.data_is_empty() {
+            ctx.accounts.initialize_user_metadata(&ctx.bumps)?;
+        }
+        if ctx.accounts.obligation.data_is_empty() {
+            ctx.accounts.initialize_obligation(&ctx.bumps)?;
+        }
+        if ctx.accounts.obligation_farm_state.data_is_empty() {
+            ctx.accounts.initialize_obligation_farms_for_reserve()?;
         }
-        ctx.accounts.refresh_obligation(has_collateral_or_borrows_flags)?;
-        ctx.accounts.deposit(amount)
+
+        Ok(())
     }
 
-    pub fn looping<'info>(ctx: Context<'_, '_, '_, 'info, Looping<'info>>, has_collateral_or_borrows_flags: u8, swap_data: Vec<u8>, amount: u64) -> Result<()> {
+    pub fn deposit<'info>(ctx: Context<'_, '_, '_, 'info, Deposit<'info>>, amount: u64) -> Result<()> {
+        let (reserves, _) = split_obligation_reserves(&ctx.accounts.obligation, ctx.remaining_accounts)?;
+
+        ctx.accounts.transfer_from_owner(amount)?;
+        ctx.accounts.refresh_position(reserves)?;
+        let ltv_before_bps = Obligation::load(&ctx.accounts.obligation)?.ltv_bps()?;
+        ctx.accounts.deposit(amount, &ctx.bumps)?;
+
+        // Leave the position under the market max LTV
+        ctx.accounts.refresh_position(reserves)?;
+        let ltv_after_bps = check_health(&ctx.accounts.obligation, ltv_before_bps, ctx.accounts.market_config.max_ltv_bps)?;
+
+        emit_cpi!(Deposited {
+            owner: ctx.accounts.owner.key(),
+            lending_market: ctx.accounts.lending_market.key(),
+            reserve: ctx.accounts.reserve_collateral.key(),
+            mint: ctx.accounts.reserve_liquidity_mint.key(),
+            amount,
+            ltv_before_bps,
+            ltv_after_bps,
+        });
+
+        Ok(())
+    }
+
+    pub fn looping<'info>(ctx: Context<'_, '_, '_, 'info, Looping<'info>>, swap_data: Vec<u8>, slippage_bps: u16, amount: u64) -> Result<()> {
+        // The obligation reserves come first in the remaining accounts, the Jupiter route accounts after them
+        let (reserves, route_accounts) = split_obligation_reserves(&ctx.accounts.obligation, ctx.remaining_accounts)?;
+
         // Borrow the collateral to swap
-        ctx.accounts.refresh_reserve_collateral()?;
-        ctx.accounts.refresh_reserve_borrow()?;
-        ctx.accounts.refresh_obligation(has_collateral_or_borrows_flags)?;
-        ctx.accounts.borrow_from_collateral(amount)?;
+        ctx.accounts.refresh_position(reserves)?;
+        let ltv_before_bps = Obligation::load(&ctx.accounts.obligation)?.ltv_bps()?;
+        ctx.accounts.borrow_from_collateral(amount, &ctx.bumps)?;
+
+        // Take the borrow fee, the route swaps what is left
+        let fee = Config::fee(amount, ctx.accounts.config.borrow_fee_bps)?;
+        ctx.accounts.pay_fee(fee, &ctx.bumps)?;
 
         // Swap the collateral
-        ctx.accounts.swap_collateral(&swap_data, amount, ctx.remaining_accounts)?;
+        let swap = ctx.accounts.swap_collateral(&swap_data, amount.checked_sub(fee).ok_or(error!(LoopingError::MathOverflow))?, slippage_bps, route_accounts, &ctx.bumps)?;
         
         // Deposit Back the newly swapped collateral
-        ctx.accounts.refresh_reserve_collateral()?;
-        ctx.accounts.refresh_reserve_borrow()?;
-        ctx.accounts.refresh_obligation(FLAG_HAS_BORROWS)?;
-        ctx.accounts.deposit()
+        ctx.accounts.refresh_position(reserves)?;
+        ctx.accounts.deposit(swap.received, &ctx.bumps)?;
+
+        // Leave the position under the market max LTV and the pair max leverage
+        ctx.accounts.refresh_position(reserves)?;
+        let ltv_after_bps = check_health(&ctx.accounts.obligation, ltv_before_bps, ctx.accounts.market_config.max_ltv_bps)?;
+        let reserve_pair = ctx.accounts.market_config.reserve_pair(&ctx.accounts.position.reserve_collateral, &ctx.accounts.position.reserve_borrow)?;
+        check_leverage(&ctx.accounts.obligation, reserve_pair.max_leverage_bps)?;
+
+        emit_cpi!(Looped {
+            owner: ctx.accounts.owner.key(),
+            lending_market: ctx.accounts.lending_market.key(),
+            reserve_collateral: ctx.accounts.reserve_collateral.key(),
+            reserve_borrow: ctx.accounts.reserve_borrow.key(),
+            collateral_mint: ctx.accounts.output_mint.key(),
+            debt_mint: ctx.accounts.input_mint.key(),
+            flash_loan: false,
+            route: swap.route_kind,
+            borrowed: amount,
+            fee,
+            swapped: swap.spent,
+            deposited: swap.received,
+            ltv_before_bps,
+            ltv_after_bps,
+        });
+
+        Ok(())
     }
 
-    pub fn repay<'info>(ctx: Context<'_, '_, '_, 'info, Repay<'info>>, swap_data: Vec<u8>, in_amount: u64, out_amount: u64) -> Result<()> {
+    pub fn leverage<'info>(ctx: Context<'_, '_, '_, 'info, Leverage<'info>>, swap_data: Vec<u8>, slippage_bps: u16, target_leverage_bps: u32) -> Result<()> {
+        let max_leverage_bps = ctx.accounts.market_config.reserve_pair(&ctx.accounts.position.reserve_collateral, &ctx.accounts.position.reserve_borrow)?.max_leverage_bps;
+        require_gte!(max_leverage_bps, target_leverage_bps, LoopingError::LeverageTooHigh);
+
+        // The obligation reserves come first in the remaining accounts, the Jupiter route accounts after them
+        let (reserves, route_accounts) = split_obligation_reserves(&ctx.accounts.obligation, ctx.remaining_accounts)?;
+
+        // Work out the debt that takes the position to the target leverage, on freshly refreshed reserves
+        ctx.accounts.refresh_position(reserves)?;
+        let ltv_before_bps = Obligation::load(&ctx.accounts.obligation)?.ltv_bps()?;
+        let amount = ctx.accounts.leverage_amount(target_leverage_bps)?;
+
+        // The client wraps this instruction in a Kamino flash borrow/repay that covers `amount`
+        ctx.accounts.check_flash_loan(amount)?;
+        ctx.accounts.transfer_from_owner(amount)?;
+
+        // Swap it all at once, the route the client quoted is resized to `amount`
+        let swap_data = resize_exact_in_route(&swap_data, amount)?;
+        let swap = ctx.accounts.swap_collateral(&swap_data, amount, slippage_bps, route_accounts, &ctx.bumps)?;
+
+        // Deposit the swapped collateral
+        ctx.accounts.refresh_position(reserves)?;
+        ctx.accounts.deposit(swap.received, &ctx.bumps)?;
+
+        // Borrow against the new collateral, on top of the borrow fee, and hand it back for the flash repay
+        ctx.accounts.refresh_position(reserves)?;
+        let fee = Config::fee(amount, ctx.accounts.config.borrow_fee_bps)?;
+        let borrowed = amount.checked_add(fee).ok_or(error!(LoopingError::MathOverflow))?;
+        ctx.accounts.borrow_from_collateral(borrowed, &ctx.bumps)?;
+        ctx.accounts.pay_fee(fee, &ctx.bumps)?;
+
+        // Leave the position under the market max LTV and the pair max leverage
+        ctx.accounts.refresh_position(reserves)?;
+        let ltv_after_bps = check_health(&ctx.accounts.obligation, ltv_before_bps, ctx.accounts.market_config.max_ltv_bps)?;
+        check_leverage(&ctx.accounts.obligation, max_leverage_bps)?;
+
+        emit_cpi!(Looped {
+            owner: ctx.accounts.owner.key(),
+            lending_market: ctx.accounts.lending_market.key(),
+            reserve_collateral: ctx.accounts.reserve_collateral.key(),
+            reserve_borrow: ctx.accounts.reserve_borrow.key(),
+            collateral_mint: ctx.accounts.output_mint.key(),
+            debt_mint: ctx.accounts.input_mint.key(),
+            flash_loan: true,
+            route: swap.route_kind,
+            borrowed,
+            fee,
+            swapped: swap.spent,
+            deposited: swap.received,
+            ltv_before_bps,
+            ltv_after_bps,
+        });
+
+        ctx.accounts.transfer_to_owner(amount, &ctx.bumps)
+    }
+
+    pub fn repay<'info>(ctx: Context<'_, '_, '_, 'info, Repay<'info>>, swap_data: Vec<u8>, slippage_bps: u16, in_amount: u64, out_amount: u64) -> Result<()> {
+        // The obligation reserves come first in the remaining accounts, the Jupiter route accounts after them
+        let (reserves, route_accounts) = split_obligation_reserves(&ctx.accounts.obligation, ctx.remaining_accounts)?;
+
         // Withdraw the collateral to swap
-        ctx.accounts.refresh_reserve_collateral()?;
-        ctx.accounts.refresh_reserve_borrow()?;
-        ctx.accounts.refresh_obligation()?;
-        ctx.accounts.withdraw_collateral(in_amount)?;
+        ctx.accounts.refresh_position(reserves)?;
+        let ltv_before_bps = Obligation::load(&ctx.accounts.obligation)?.ltv_bps()?;
+        let withdrawn = ctx.accounts.withdraw_collateral(in_amount, &ctx.bumps)?;
 
         // Swap the collateral
-        ctx.accounts.swap_for_collateral(&swap_data, out_amount, ctx.remaining_accounts)?;
+        let swap = ctx.accounts.swap_for_collateral(&swap_data, out_amount, slippage_bps, route_accounts, &ctx.bumps)?;
+
+        // Repay the debt, leaving room in what the swap delivered for the repay fee
+        ctx.accounts.refresh_position(reserves)?;
+        let max_repay = swap.received.checked_sub(Config::fee(swap.received, ctx.accounts.config.repay_fee_bps)?).ok_or(error!(LoopingError::MathOverflow))?;
+        let repaid = ctx.accounts.repay_debt(max_repay, &ctx.bumps)?;
+
+        // Take the repay fee on what was actually repaid out of what the swap delivered
+        let fee = Config::fee(repaid, ctx.accounts.config.repay_fee_bps)?;
+        require_gte!(swap.received.checked_sub(repaid).ok_or(error!(LoopingError::MathOverflow))?, fee, LoopingError::RepayFeeNotCovered);
+        ctx.accounts.pay_fee(fee, &ctx.bumps)?;
+
+        // Put back the collateral the exact-out route didn't need, the route can't dip into what the vault held before
+        require_gte!(withdrawn, swap.spent, LoopingError::SwapSpentAboveWithdrawn);
+        ctx.accounts.refresh_position(reserves)?;
+        ctx.accounts.redeposit(withdrawn.checked_sub(swap.spent).ok_or(error!(LoopingError::MathOverflow))?, &ctx.bumps)?;
+
+        // Leave the position under the market max LTV
+        ctx.accounts.refresh_position(reserves)?;
+        let ltv_after_bps = check_health(&ctx.accounts.obligation, ltv_before_bps, ctx.accounts.market_config.max_ltv_bps)?;
+
+        emit_cpi!(Repaid {
+            owner: ctx.accounts.owner.key(),
+            lending_market: ctx.accounts.lending_market.key(),
+            reserve_collateral: ctx.accounts.reserve_collateral.key(),
+            reserve_borrow: ctx.accounts.reserve_borrow.key(),
+            collateral_mint: ctx.accounts.input_mint.key(),
+            debt_mint: ctx.accounts.output_mint.key(),
+            flash_loan: false,
+            route: swap.route_kind,
+            withdrawn,
+            swapped: swap.spent,
+            repaid,
+            fee,
+            ltv_before_bps,
+            ltv_after_bps,
+        });
+
+        Ok(())
+    }
+
+    pub fn deleverage<'info>(ctx: Context<'_, '_, '_, 'info, Deleverage<'info>>, swap_data: Vec<u8>, slippage_bps: u16, amount: u64, in_amount: u64, repay_all: bool) -> Result<()> {
+        // The obligation reserves come first in the remaining accounts, the Jupiter route accounts after them
+        let (reserves, route_accounts) = split_obligation_reserves(&ctx.accounts.obligation, ctx.remaining_accounts)?;
+
+        // The client wraps this instruction in a Kamino flash borrow/repay of `amount`
+        ctx.accounts.check_flash_loan(amount)?;
+        ctx.accounts.transfer_from_owner(amount)?;
+
+        // Repay the debt with the flash loan
+        ctx.accounts.refresh_position(reserves)?;
+        let ltv_before_bps = Obligation::load(&ctx.accounts.obligation)?.ltv_bps()?;
+        let repaid = ctx.accounts.repay_debt(if repay_all { u64::MAX } else { amount }, &ctx.bumps)?;
+
+        // Withdraw the freed collateral
+        ctx.accounts.refresh_position(reserves)?;
+        let withdrawn = ctx.accounts.withdraw_collateral(in_amount, &ctx.bumps)?;
+
+        // Swap back at least what the repay used and the repay fee on it, and take the fee
+        let fee = Config::fee(repaid, ctx.accounts.config.repay_fee_bps)?;
+        let swap = ctx.accounts.swap_for_debt(&swap_data, repaid.checked_add(fee).ok_or(error!(LoopingError::MathOverflow))?, slippage_bps, route_accounts, &ctx.bumps)?;
+        ctx.accounts.pay_fee(fee, &ctx.bumps)?;
+
+        // Put back the collateral the exact-out route didn't need, the route can't dip into what the vault held before
+        require_gte!(withdrawn, swap.spent, LoopingError::SwapSpentAboveWithdrawn);
+        ctx.accounts.refresh_position(reserves)?;
+        ctx.accounts.redeposit(withdrawn.checked_sub(swap.spent).ok_or(error!(LoopingError::MathOverflow))?, &ctx.bumps)?;
+
+        // Leave the position under the market max LTV
+        ctx.accounts.refresh_position(reserves)?;
+        let ltv_after_bps = check_health(&ctx.accounts.obligation, ltv_before_bps, ctx.accounts.market_config.max_ltv_bps)?;
+
+        emit_cpi!(Repaid {
+            owner: ctx.accounts.owner.key(),
+            lending_market: ctx.accounts.lending_market.key(),
+            reserve_collateral: ctx.accounts.reserve_collateral.key(),
+            reserve_borrow: ctx.accounts.reserve_borrow.key(),
+            collateral_mint: ctx.accounts.input_mint.key(),
+            debt_mint: ctx.accounts.output_mint.key(),
+            flash_loan: true,
+            route: swap.route_kind,
+            withdrawn,
+            swapped: swap.spent,
+            repaid,
+            fee,
+            ltv_before_bps,
+            ltv_after_bps,
+        });
+
+        // Hand back the swap output net of the fee, plus what the repay left of the flash loan. It covers the
+        // flash repay, any surplus of the route stays with the owner
+        let flash_repay = amount
+            .checked_sub(repaid)
+            .and_then(|unused| unused.checked_add(swap.received))
+            .and_then(|total| total.checked_sub(fee))
+            .ok_or(error!(LoopingError::MathOverflow))?;
+        ctx.accounts.transfer_to_owner(flash_repay, &ctx.bumps)
+    }
+
+    pub fn withdraw<'info>(ctx: Context<'_, '_, '_, 'info, Withdraw<'info>>, amount: u64) -> Result<()> {
+        let (reserves, _) = split_obligation_reserves(&ctx.accounts.obligation, ctx.remaining_accounts)?;
+
+        ctx.accounts.refresh_position(reserves)?;
+        let ltv_before_bps = Obligation::load(&ctx.accounts.obligation)?.ltv_bps()?;
+        let withdrawn = ctx.accounts.withdraw_collateral(amount, &ctx.bumps)?;
+
+        // Leave the position under the market max LTV and the pair max leverage
+        ctx.accounts.refresh_position(reserves)?;
+        let ltv_after_bps = check_health(&ctx.accounts.obligation, ltv_before_bps, ctx.accounts.market_config.max_ltv_bps)?;
+        let reserve_pair = ctx.accounts.market_config.reserve_pair(&ctx.accounts.position.reserve_collateral, &ctx.accounts.position.reserve_borrow)?;
+        check_leverage(&ctx.accounts.obligation, reserve_pair.max_leverage_bps)?;
+
+        emit_cpi!(Withdrawn {
+            owner: ctx.accounts.owner.key(),
+            lending_market: ctx.accounts.lending_market.key(),
+            reserve: ctx.accounts.reserve_collateral.key(),
+            mint: ctx.accounts.reserve_liquidity_mint.key(),
+            amount: withdrawn,
+            ltv_before_bps,
+            ltv_after_bps,
+        });
+
+        ctx.accounts.transfer_to_owner(withdrawn, &ctx.bumps)
+    }
 
-        // Repay the debt
-        ctx.accounts.refresh_reserve_collateral()?;
-        ctx.accounts.refresh_reserve_borrow()?;
-        ctx.accounts.refresh_obligation()?;
-        ctx.accounts.repay_debt()
+    pub fn harvest<'info>(ctx: Context<'_, '_, '_, 'info, Harvest<'info>>) -> Result<()> {
+        // Every initialized reward of the farm, each with its accounts in the remaining accounts
+        for reward in ctx.accounts.reward_accounts(ctx.remaining_accounts)? {
+            let harvested = ctx.accounts.harvest(&reward, &ctx.bumps)?;
+
+            emit_cpi!(Harvested {
+                owner: ctx.accounts.owner.key(),
+                obligation: ctx.accounts.position.obligation,
+                farm_state: ctx.accounts.reserve_farm_state.key(),
+                reward_mint: reward.reward_mint.key(),
+                reward_index: reward.reward_index,
+                amount: harvested,
+            });
+
+            ctx.accounts.transfer_to_owner(&reward, harvested, &ctx.bumps)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn compound<'info>(ctx: Context<'_, '_, '_, 'info, Compound<'info>>, swap_data: Vec<u8>, slippage_bps: u16, amount: u64, reward_index: u64) -> Result<()> {
+        ctx.accounts.check_reserve_reward()?;
+
+        // The obligation reserves come first in the remaining accounts, the Jupiter route accounts after them
+        let (reserves, route_accounts) = split_obligation_reserves(&ctx.accounts.obligation, ctx.remaining_accounts)?;
+
+        // Harvest into the reward vault
+        let harvested = ctx.accounts.harvest(reward_index, &ctx.bumps)?;
+
+        // Swap the rewards, priced against freshly refreshed reserves
+        ctx.accounts.refresh_position(reserves)?;
+        ctx.accounts.refresh_reserve_reward()?;
+        let swap = ctx.accounts.swap_rewards(&swap_data, amount, slippage_bps, route_accounts, &ctx.bumps)?;
+
+        // Tip the keeper on the collateral the swap delivered, so a poor route shrinks the tip too
+        let tip = Config::fee(swap.received, ctx.accounts.config.compound_tip_bps)?;
+        ctx.accounts.pay_tip(tip, &ctx.bumps)?;
+
+        // Deposit the rest of the swapped collateral, which only lowers the LTV
+        let deposited = swap.received.checked_sub(tip).ok_or(error!(LoopingError::MathOverflow))?;
+        ctx.accounts.deposit(deposited, &ctx.bumps)?;
+
+        emit_cpi!(Compounded {
+            owner: ctx.accounts.owner.key(),
+            keeper: ctx.accounts.keeper.key(),
+            lending_market: ctx.accounts.lending_market.key(),
+            reserve_collateral: ctx.accounts.reserve_collateral.key(),
+            farm_state: ctx.accounts.reserve_farm_state.key(),
+            reward_mint: ctx.accounts.reward_mint.key(),
+            collateral_mint: ctx.accounts.collateral_mint.key(),
+            route: swap.route_kind,
+            harvested,
+            tip,
+            swapped: swap.spent,
+            deposited,
+        });
+
+        Ok(())
+    }
+
+    pub fn close_position<'info>(ctx: Context<'_, '_, '_, 'info, ClosePosition<'info>>, swap_data: Vec<u8>, slippage_bps: u16, in_amount: u64, out_amount: u64) -> Result<()> {
+        // The obligation reserves come first in the remaining accounts, the Jupiter route accounts after them
+        let (reserves, route_accounts) = split_obligation_reserves(&ctx.accounts.obligation, ctx.remaining_accounts)?;
+
+        ctx.accounts.refresh_position(reserves)?;
+        let ltv_before_bps = Obligation::load(&ctx.accounts.obligation)?.ltv_bps()?;
+
+        // Unwind the leverage (skipped when the position has no debt left)
+        let has_debt = Obligation::load(&ctx.accounts.obligation)?.borrows().any(|borrow| borrow.borrowed_amount_sf > 0);
+        require!(!has_debt || !swap_data.is_empty(), LoopingError::PositionNotUnwound);
+        let (route, unwound, swapped, repaid) = if !swap_data.is_empty() {
+            let withdrawn = ctx.accounts.withdraw_collateral(in_amount, &ctx.bumps)?;
+
+            let swap = ctx.accounts.swap_for_debt(&swap_data, out_amount, slippage_bps, route_accounts, &ctx.bumps)?;
+
+            ctx.accounts.refresh_position(reserves)?;
+            let repaid = ctx.accounts.repay_debt(&ctx.bumps)?;
+
+            (Some(swap.route_kind), withdrawn, swap.spent, repaid)
+        } else {
+            (None, 0, 0, 0)
+        };
+
+        // Withdraw all the remaining collateral
+        ctx.accounts.refresh_position(reserves)?;
+        let withdrawn = ctx.accounts.withdraw_collateral(u64::MAX, &ctx.bumps)?;
+        check_unwound(&ctx.accounts.obligation, &ctx.accounts.position.reserve_collateral)?;
+
+        emit_cpi!(PositionClosed {
+            owner: ctx.accounts.owner.key(),
+            lending_market: ctx.accounts.lending_market.key(),
+            obligation: ctx.accounts.obligation.key(),
+            collateral_mint: ctx.accounts.collateral_mint.key(),
+            debt_mint: ctx.accounts.debt_mint.key(),
+            route,
+            withdrawn: unwound + withdrawn,
+            swapped,
+            repaid,
+            ltv_before_bps,
+        });
+
+        // Send everything back to the owner and reclaim the rent
+        ctx.accounts.sweep_vaults(&ctx.bumps)
+    }
+
+    pub fn initialize_config(ctx: Context<InitializeConfig>, admin: Pubkey) -> Result<()> {
+        ctx.accounts.initialize_config(admin, &ctx.bumps)
+    }
+
+    pub fn update_config(ctx: Context<UpdateConfig>, pending_admin: Option<Pubkey>, paused: PauseFlags, borrow_fee_bps: u16, repay_fee_bps: u16, max_platform_fee_bps: u8, compound_tip_bps: u16) -> Result<()> {
+        ctx.accounts.update_config(pending_admin, paused, borrow_fee_bps, repay_fee_bps, max_platform_fee_bps, compound_tip_bps)
+    }
+
+    pub fn set_max_reward_slippage(ctx: Context<UpdateConfig>, max_reward_slippage_bps: u16) -> Result<()> {
+        ctx.accounts.set_max_reward_slippage(max_reward_slippage_bps)
+    }
+
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+        ctx.accounts.accept_admin()
+    }
+
+    pub fn withdraw_fees(ctx: Context<WithdrawFees>, amount: u64) -> Result<()> {
+        ctx.accounts.withdraw_fees(amount, &ctx.bumps)
+    }
+
+    pub fn initialize_referrer(ctx: Context<InitializeReferrer>) -> Result<()> {
+        // One referrer user metadata, then one referrer token state per borrow reserve
+        if ctx.accounts.referrer_user_metadata.data_is_empty() {
+            ctx.accounts.initialize_user_metadata(&ctx.bumps)?;
+        }
+        if ctx.accounts.referrer_token_state.data_is_empty() {
+            ctx.accounts.initialize_referrer_token_state()?;
+        }
+
+        Ok(())
+    }
+
+    pub fn withdraw_referrer_fees(ctx: Context<WithdrawReferrerFees>) -> Result<()> {
+        let amount = ctx.accounts.withdraw_referrer_fees(&ctx.bumps)?;
+        ctx.accounts.transfer_to_treasury(amount, &ctx.bumps)
+    }
+
+    pub fn initialize_market_config(ctx: Context<InitializeMarketConfig>, max_price_deviation_bps: u16, max_ltv_bps: u16) -> Result<()> {
+        ctx.accounts.initialize_market_config(max_price_deviation_bps, max_ltv_bps, &ctx.bumps)
+    }
+
+    pub fn update_market_config(ctx: Context<UpdateMarketConfig>, max_price_deviation_bps: u16, max_ltv_bps: u16) -> Result<()> {
+        ctx.accounts.update_market_config(max_price_deviation_bps, max_ltv_bps)
+    }
+
+    pub fn set_reserve_pair(ctx: Context<SetReservePair>, reserve_pair: ReservePair) -> Result<()> {
+        ctx.accounts.set_reserve_pair(reserve_pair)
+    }
+
+    pub fn remove_reserve_pair(ctx: Context<UpdateMarketConfig>, reserve_collateral: Pubkey, reserve_borrow: Pubkey) -> Result<()> {
+        ctx.accounts.remove_reserve_pair(reserve_collateral, reserve_borrow)
+    }
+
+    pub fn set_delegate(ctx: Context<SetDelegate>, delegate: Option<Pubkey>) -> Result<()> {
+        ctx.accounts.set_delegate(delegate)
     }
 }
+
@@ -0,0 +1,59 @@
+use anchor_lang::prelude::*;
+use crate::error::LoopingError;
+
+/// # Config
+/// 
+/// Program-wide settings, a single account owned by the admin.
+/// 
+/// The pause flags let the admin stop an instruction without redeploying (e.g. while Kamino or
+/// Jupiter has an incident). Closing a position stays possible unless repay or withdraw is paused.
+/// 
+/// The fees go to the treasury, one associated token account per mint owned by the `[b"treasury"]` PDA.
+/// Closing a position is free so that leaving never costs more than the swap.
+#[account]
+#[derive(InitSpace)]
+pub struct Config {
+    /// Wallet allowed to update the config and the market configs.
+    pub admin: Pubkey,
+    /// Wallet the admin handed over to, `Pubkey::default()` if none. It becomes the admin once it accepts.
+    pub pending_admin: Pubkey,
+    pub paused: PauseFlags,
+    /// Fee taken on the amount borrowed by `looping` and `leverage`, paid in the debt mint.
+    pub borrow_fee_bps: u16,
+    /// Fee taken on the amount repaid by `repay` and `deleverage`, paid in the debt mint.
+    pub repay_fee_bps: u16,
+    /// Highest Jupiter platform fee a route can carry, skimmed by Jupiter into the treasury.
+    pub max_platform_fee_bps: u8,
+    /// Tip paid to the keeper of `compound`, taken on the collateral the reward swap delivers.
+    pub compound_tip_bps: u16,
+    /// Highest slippage a keeper can request on the `compound` reward swaps. Rewards are often thinly
+    /// traded, so they get their own bound rather than the one of the reserve pair.
+    pub max_reward_slippage_bps: u16,
+    pub bump: u8,
+}
+
+/// # Pause Flags
+/// 
+/// Which instructions the admin has stopped.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct PauseFlags {
+    pub deposit: bool,
+    /// Pauses `looping` and `leverage`.
+    pub looping: bool,
+    /// Pauses `repay` and `deleverage`.
+    pub repay: bool,
+    /// Pauses `withdraw` and `harvest`.
+    pub withdraw: bool,
+}
+
+/// Highest fee the admin can set, 10%.
+pub const MAX_FEE_BPS: u16 = 1_000;
+
+impl Config {
+    /// Fee owed on `amount` at `fee_bps`, rounded down.
+    pub fn fee(amount: u64, fee_bps: u16) -> Result<u64> {
+        let fee = (amount as u128).checked_mul(fee_bps as u128).ok_or(error!(LoopingError::MathOverflow))? / 10_000;
+
+        Ok(fee as u64)
+    }
+}
@@ -0,0 +1,73 @@
+use anchor_lang::prelude::*;
+use crate::error::LoopingError;
+
+/// # Market Config
+/// 
+/// Risk parameters the program enforces for every position opened on a single Kamino lending market.
+/// 
+/// Only the reserve pairs listed here can be looped through the program. Removing a pair locks the
+/// positions opened on it, to wind one down set its max leverage to 1x (`10_000`) instead.
+#[account]
+#[derive(InitSpace)]
+pub struct MarketConfig {
+    pub lending_market: Pubkey,
+    /// Largest gap allowed between the swap price and the Kamino oracle price.
+    pub max_price_deviation_bps: u16,
+    /// Highest LTV a position can be left at, as the debt over the value the collateral allows to borrow.
+    pub max_ltv_bps: u16,
+    #[max_len(MAX_RESERVE_PAIRS)]
+    pub reserve_pairs: Vec<ReservePair>,
+    pub bump: u8,
+}
+
+pub const MAX_RESERVE_PAIRS: usize = 8;
+
+/// 100%, the upper bound of every basis points limit of the market.
+pub const MAX_BPS: u16 = 10_000;
+
+/// # Reserve Pair
+/// 
+/// A collateral/debt couple of reserves of the market that positions are allowed to loop.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace)]
+pub struct ReservePair {
+    pub reserve_collateral: Pubkey,
+    pub reserve_borrow: Pubkey,
+    pub collateral_mint: Pubkey,
+    pub debt_mint: Pubkey,
+    /// Highest leverage a position can be looped to, as the deposited value over the equity (`20_000` is 2x).
+    pub max_leverage_bps: u32,
+    /// Highest slippage a caller can request on the swaps of this pair.
+    pub max_slippage_bps: u16,
+}
+
+impl MarketConfig {
+    /// # Check the market limits
+    /// 
+    /// A zero max LTV would lock every position, anything above 100% can't be reached before Kamino liquidates.
+    pub fn check_limits(max_price_deviation_bps: u16, max_ltv_bps: u16) -> Result<()> {
+        require_gte!(MAX_BPS, max_price_deviation_bps, LoopingError::InvalidMarketConfig);
+        require!(max_ltv_bps > 0 && max_ltv_bps <= MAX_BPS, LoopingError::InvalidMarketConfig);
+
+        Ok(())
+    }
+
+    /// The listed pair for these reserves, `MarketNotAllowed` if there is none.
+    pub fn reserve_pair(&self, reserve_collateral: &Pubkey, reserve_borrow: &Pubkey) -> Result<&ReservePair> {
+        self.reserve_pairs
+            .iter()
+            .find(|pair| pair.reserve_collateral == *reserve_collateral && pair.reserve_borrow == *reserve_borrow)
+            .ok_or(error!(LoopingError::MarketNotAllowed))
+    }
+}
+
+impl ReservePair {
+    /// # Check the pair limits
+    /// 
+    /// The max leverage can't go under 1x, and no swap can lose more than what it spends.
+    pub fn check_limits(&self) -> Result<()> {
+        require_gte!(self.max_leverage_bps, MAX_BPS as u32, LoopingError::InvalidReservePair);
+        require_gte!(MAX_BPS, self.max_slippage_bps, LoopingError::InvalidReservePair);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,8 @@
+pub mod position;
+pub use position::*;
+
+pub mod market_config;
+pub use market_config::*;
+
+pub mod config;
+pub use config::*;
@@ -0,0 +1,35 @@
+use anchor_lang::prelude::*;
+
+/// # Position
+/// 
+/// Mirror of the Kamino obligation owned by the protocol authority of a single wallet.
+/// 
+/// Indexers and frontends can read this account instead of decoding the Kamino layouts. 
+/// The amounts are cumulative and expressed in the liquidity mint of the reserve they 
+/// refer to (collateral mint for deposited/withdrawn, debt mint for borrowed/repaid).
+#[account]
+#[derive(InitSpace)]
+pub struct Position {
+    pub owner: Pubkey,
+    /// Wallet allowed to loop and repay on behalf of the owner, `Pubkey::default()` if none.
+    pub delegate: Pubkey,
+    pub lending_market: Pubkey,
+    pub obligation: Pubkey,
+    pub reserve_collateral: Pubkey,
+    pub reserve_borrow: Pubkey,
+    pub collateral_mint: Pubkey,
+    pub debt_mint: Pubkey,
+    pub deposited: u64,
+    pub withdrawn: u64,
+    pub borrowed: u64,
+    pub repaid: u64,
+    pub authority_bump: u8,
+    pub bump: u8,
+}
+
+impl Position {
+    /// Whether `key` is allowed to change the leverage of the position.
+    pub fn is_owner_or_delegate(&self, key: &Pubkey) -> bool {
+        *key == self.owner || (self.delegate != Pubkey::default() && *key == self.delegate)
+    }
+}
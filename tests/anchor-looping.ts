@@ -2,9 +2,10 @@ import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { Connection, Keypair, LAMPORTS_PER_SOL, PublicKey, SYSVAR_RENT_PUBKEY, SYSVAR_INSTRUCTIONS_PUBKEY, SystemProgram, AddressLookupTableProgram, Transaction, CreateLookupTableParams, ExtendLookupTableParams, ComputeBudgetProgram, TransactionMessage, AddressLookupTableAccount, VersionedTransaction } from "@solana/web3.js";
 import { AnchorLooping } from "../target/types/anchor_looping";
-import { getAssociatedTokenAddressSync, createAssociatedTokenAccountIdempotentInstruction, TOKEN_PROGRAM_ID } from "@solana/spl-token";
-import { CBBTC_COLLATERAL_FARM_ADDRESS, LENDING_MARKET, obligationAccount, obligationFarmStatePdaAccount, userMetadataAccount, LENDING_MARKET_AUTH, CBBTC_RESERVE, K_LEND_PROGRAM_ID, K_FARMS_PROGRAM_ID, USDC_RESERVE, SCOPE_ORACLE_ACCOUNT, CBBTC_SUPPLY_VAULT, CBBTC_COLLATERAL_MINT, CBBTC_COLLATERAL_VAULT, hasCollateralOrBorrows, USDC_FEE_RECEIVER, USDC_SUPPLY_VAULT, calcuateRepaymentAmount,  } from "./kamino";
+import { getAssociatedTokenAddressSync, createAssociatedTokenAccountIdempotentInstruction, TOKEN_PROGRAM_ID, ASSOCIATED_TOKEN_PROGRAM_ID } from "@solana/spl-token";
+import { CBBTC_COLLATERAL_FARM_ADDRESS, LENDING_MARKET, obligationAccount, obligationFarmStatePdaAccount, userMetadataAccount, referrerTokenStateAccount, LENDING_MARKET_AUTH, CBBTC_RESERVE, K_LEND_PROGRAM_ID, K_FARMS_PROGRAM_ID, USDC_RESERVE, SCOPE_ORACLE_ACCOUNT, CBBTC_SUPPLY_VAULT, CBBTC_COLLATERAL_MINT, CBBTC_COLLATERAL_VAULT, USDC_FEE_RECEIVER, USDC_SUPPLY_VAULT, calcuateRepaymentAmount, farmRewardAccounts, farmRewardCount, obligationReserveAccounts, obligationValues, flashBorrowUsdcIx, flashRepayUsdcIx } from "./kamino";
 import { extractRemainingAccountsForSwap, jupiterEventAuthority, jupiterProgramId, swap } from "./jup";
+import { expect } from "chai";
 // Surfnet Helpers
 const surfnetAirdrop = async (connection: Connection, address: string, lamports: number) => {
   const call = {
@@ -55,34 +56,191 @@ describe("anchor-looping", () => {
   const cbBtcMint = new PublicKey("cbbtcf3aa214zXHbiAZQwf4122FBYbraNdFqgw4iMij");
 
   // Protocol Accounts
-  const protocolAuthority = PublicKey.findProgramAddressSync([Buffer.from("auth")], program.programId)[0];
+  const protocolAuthority = PublicKey.findProgramAddressSync([Buffer.from("auth"), payer.toBuffer()], program.programId)[0];
   const usdcVault = getAssociatedTokenAddressSync(usdcMint, protocolAuthority, true);
   const cbBtcVault = getAssociatedTokenAddressSync(cbBtcMint, protocolAuthority, true);
+  const position = PublicKey.findProgramAddressSync([Buffer.from("position"), payer.toBuffer(), LENDING_MARKET.toBuffer()], program.programId)[0];
+  const config = PublicKey.findProgramAddressSync([Buffer.from("config")], program.programId)[0];
+  const marketConfig = PublicKey.findProgramAddressSync([Buffer.from("market_config"), LENDING_MARKET.toBuffer()], program.programId)[0];
+  const programData = PublicKey.findProgramAddressSync([program.programId.toBuffer()], new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111"))[0];
+  const treasury = PublicKey.findProgramAddressSync([Buffer.from("treasury")], program.programId)[0];
+  const usdcTreasuryVault = getAssociatedTokenAddressSync(usdcMint, treasury, true);
+  const cbBtcTreasuryVault = getAssociatedTokenAddressSync(cbBtcMint, treasury, true);
+  const referrer = PublicKey.findProgramAddressSync([Buffer.from("referrer")], program.programId)[0];
+  const referrerUserMetadata = userMetadataAccount(referrer);
+  const usdcReferrerTokenState = referrerTokenStateAccount(referrer, USDC_RESERVE);
+  const eventAuthority = PublicKey.findProgramAddressSync([Buffer.from("__event_authority")], program.programId)[0];
+
+  // Slippage of every Jupiter quote, has to fit under the reserve pair maximum
+  const slippageBps = 50;
+
+  // Protocol fee on the borrowed notional
+  const borrowFeeBps = 10;
+  // Jupiter platform fee skimmed into the treasury on the looping and repay swaps
+  const platformFeeBps = 10;
+  // Tip paid to the compound keeper on the rewards it swaps
+  const compoundTipBps = 50;
 
   it("Setup", async () => {
     // Airdrop to payer
     await surfnetAirdrop(program.provider.connection, payer.toString(), 1_000 * LAMPORTS_PER_SOL);
   });
 
+  it("Initialize the config", async () => {
+    // The provider wallet deployed the program and is its upgrade authority, it keeps the admin role
+    await program.methods.initializeConfig(program.provider.publicKey)
+    .accountsStrict({
+      authority: program.provider.publicKey,
+      config,
+      program: program.programId,
+      programData,
+      systemProgram: SystemProgram.programId,
+    })
+    .rpc({ skipPreflight: true });
+  });
+
+  it("Initialize the market config", async () => {
+    await program.methods.initializeMarketConfig(100, 8_000)
+    .accountsStrict({
+      admin: program.provider.publicKey,
+      config,
+      marketConfig,
+      lendingMarket: LENDING_MARKET,
+      systemProgram: SystemProgram.programId,
+    })
+    .rpc({ skipPreflight: true });
+  });
+
+  it("List the cbBTC/USDC reserve pair", async () => {
+    await program.methods.setReservePair({
+      reserveCollateral: CBBTC_RESERVE,
+      reserveBorrow: USDC_RESERVE,
+      collateralMint: cbBtcMint,
+      debtMint: usdcMint,
+      maxLeverageBps: 30_000,
+      maxSlippageBps: 100,
+    })
+    .accountsStrict({
+      admin: program.provider.publicKey,
+      config,
+      marketConfig,
+      reserveCollateral: CBBTC_RESERVE,
+      reserveBorrow: USDC_RESERVE,
+    })
+    .rpc({ skipPreflight: true });
+  });
+
+  it("Reject an invalid reserve pair", async () => {
+    const reservePair = {
+      reserveCollateral: CBBTC_RESERVE,
+      reserveBorrow: USDC_RESERVE,
+      collateralMint: cbBtcMint,
+      debtMint: usdcMint,
+      maxLeverageBps: 30_000,
+      maxSlippageBps: 100,
+    };
+    const cases: [typeof reservePair, string][] = [
+      [{ ...reservePair, maxLeverageBps: 9_999 }, "InvalidReservePair"],
+      [{ ...reservePair, maxSlippageBps: 10_001 }, "InvalidReservePair"],
+      // The mints swapped around don't match the reserves liquidity mints
+      [{ ...reservePair, collateralMint: usdcMint, debtMint: cbBtcMint }, "MarketNotAllowed"],
+    ];
+
+    for (const [pair, error] of cases) {
+      const tx = await program.methods.setReservePair(pair)
+      .accountsStrict({
+        admin: program.provider.publicKey,
+        config,
+        marketConfig,
+        reserveCollateral: CBBTC_RESERVE,
+        reserveBorrow: USDC_RESERVE,
+      })
+      .transaction();
+      tx.feePayer = payer;
+      tx.recentBlockhash = (await program.provider.connection.getLatestBlockhash()).blockhash;
+
+      const simulation = await program.provider.connection.simulateTransaction(tx, [payerKeypair]);
+      expect(simulation.value.err).to.not.be.null;
+      expect(simulation.value.logs.join("\n")).to.include(`Error Code: ${error}`);
+    }
+  });
+
+  it("Initialize the referrer", async () => {
+    await program.methods.initializeReferrer()
+    .accountsStrict({
+      admin: program.provider.publicKey,
+      config,
+      referrer,
+      rent: SYSVAR_RENT_PUBKEY,
+      systemProgram: SystemProgram.programId,
+      referrerUserMetadata,
+      lendingMarket: LENDING_MARKET,
+      reserve: USDC_RESERVE,
+      referrerTokenState: usdcReferrerTokenState,
+      kaminoLendingProgram: K_LEND_PROGRAM_ID,
+    })
+    .rpc({ skipPreflight: true });
+  });
+
   let userMetadata = userMetadataAccount(protocolAuthority);
   let obligation = obligationAccount(protocolAuthority);
   let reserveFarmState = CBBTC_COLLATERAL_FARM_ADDRESS
   let obligationFarmState = obligationFarmStatePdaAccount(reserveFarmState, obligation);
 
+  const depositAccounts = {
+    payer,
+    owner: payer,
+    protocolAuthority,
+    position,
+    config,
+    marketConfig,
+    reserveLiquidityMint: cbBtcMint,
+    userSourceLiquidity: getAssociatedTokenAddressSync(cbBtcMint, payer),
+    vault: cbBtcVault,
+    instructionSysvarAccount: SYSVAR_INSTRUCTIONS_PUBKEY,
+    tokenProgram: TOKEN_PROGRAM_ID,
+    associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+    systemProgram: SystemProgram.programId,
+    userMetadata,
+    obligation,
+    lendingMarket: LENDING_MARKET,
+    lendingMarketAuthority: LENDING_MARKET_AUTH,
+    reserveCollateral: CBBTC_RESERVE,
+    reserveBorrow: USDC_RESERVE,
+    reserveLiquiditySupply: CBBTC_SUPPLY_VAULT,
+    reserveCollateralMint: CBBTC_COLLATERAL_MINT,
+    reserveDestinationDepositCollateral: CBBTC_COLLATERAL_VAULT,
+    scopeOracle: SCOPE_ORACLE_ACCOUNT,
+    obligationFarmState,
+    reserveFarmState,
+    kaminoLendingProgram: K_LEND_PROGRAM_ID,
+    farmsProgram: K_FARMS_PROGRAM_ID,
+    eventAuthority,
+    program: program.programId,
+  };
+
   it("Initialize Kamino Accounts", async () => {
     await program.methods.initialize()
     .accountsStrict({
       payer,
+      owner: payer,
       protocolAuthority,
+      position,
+      marketConfig,
+      collateralMint: cbBtcMint,
+      debtMint: usdcMint,
       instructionSysvarAccount: SYSVAR_INSTRUCTIONS_PUBKEY,
       rent: SYSVAR_RENT_PUBKEY,
       tokenProgram: TOKEN_PROGRAM_ID,
       systemProgram: SystemProgram.programId,
       userMetadata,
+      referrer,
+      referrerUserMetadata,
       obligation,
       lendingMarket: LENDING_MARKET,
       lendingMarketAuthority: LENDING_MARKET_AUTH,
       reserve: CBBTC_RESERVE,
+      reserveBorrow: USDC_RESERVE,
       obligationFarmState,
       reserveFarmState,
       kaminoLendingProgram: K_LEND_PROGRAM_ID,
@@ -93,40 +251,87 @@ describe("anchor-looping", () => {
   });
 
   it("Deposit", async () => {
-    // Airdrop cbBTC to protocol authority input vault
-    await surfnetTokenAirdrop(program.provider.connection, protocolAuthority.toString(), cbBtcMint.toString(), 100_000_000);
+    // Airdrop cbBTC to the owner wallet
+    await surfnetTokenAirdrop(program.provider.connection, payer.toString(), cbBtcMint.toString(), 100_000_000);
 
     // Deposit cbBTC to obligation
-    let flag = await hasCollateralOrBorrows(program.provider.connection, obligation);
     await program.methods.deposit(
-      flag, 
       new anchor.BN(100_000_000)
-    ).accountsStrict({
-      payer,
-      protocolAuthority,
-      reserveLiquidityMint: cbBtcMint,
-      userSourceLiquidity: cbBtcVault,
-      instructionSysvarAccount: SYSVAR_INSTRUCTIONS_PUBKEY,
-      tokenProgram: TOKEN_PROGRAM_ID,
-      userMetadata,
-      obligation,
-      lendingMarket: LENDING_MARKET,
-      lendingMarketAuthority: LENDING_MARKET_AUTH,
-      reserveCollateral: CBBTC_RESERVE,
-      reserveBorrow: USDC_RESERVE,
-      reserveLiquiditySupply: CBBTC_SUPPLY_VAULT,
-      reserveCollateralMint: CBBTC_COLLATERAL_MINT,
-      reserveDestinationDepositCollateral: CBBTC_COLLATERAL_VAULT,
-      scopeOracle: SCOPE_ORACLE_ACCOUNT,
-      obligationFarmState,
-      reserveFarmState,
-      kaminoLendingProgram: K_LEND_PROGRAM_ID,
-      farmsProgram: K_FARMS_PROGRAM_ID
-    })
+    ).accountsStrict(depositAccounts)
+    .remainingAccounts(await obligationReserveAccounts(program.provider.connection, obligation))
     .signers([payerKeypair])
     .rpc({ skipPreflight: true });
   });
 
+  it("Set and revoke a delegate", async () => {
+    const delegate = Keypair.generate().publicKey;
+
+    await program.methods.setDelegate(delegate)
+    .accountsStrict({
+      owner: payer,
+      position,
+    })
+    .signers([payerKeypair])
+    .rpc();
+
+    await program.methods.setDelegate(null)
+    .accountsStrict({
+      owner: payer,
+      position,
+    })
+    .signers([payerKeypair])
+    .rpc();
+  });
+
+  it("Pause and unpause deposits", async () => {
+    await program.methods.updateConfig(null, { deposit: true, looping: false, repay: false, withdraw: false }, 0, 0, 0, 0)
+    .accountsStrict({
+      admin: program.provider.publicKey,
+      config,
+    })
+    .rpc();
+
+    const tx = await program.methods.deposit(
+      new anchor.BN(1)
+    ).accountsStrict(depositAccounts)
+    .remainingAccounts(await obligationReserveAccounts(program.provider.connection, obligation))
+    .transaction();
+    tx.feePayer = payer;
+    tx.recentBlockhash = (await program.provider.connection.getLatestBlockhash()).blockhash;
+
+    const simulation = await program.provider.connection.simulateTransaction(tx, [payerKeypair]);
+    expect(simulation.value.err).to.not.be.null;
+    expect(simulation.value.logs.join("\n")).to.include("Error Code: Paused");
+
+    await program.methods.updateConfig(null, { deposit: false, looping: false, repay: false, withdraw: false }, 0, 0, 0, 0)
+    .accountsStrict({
+      admin: program.provider.publicKey,
+      config,
+    })
+    .rpc();
+  });
+
+  it("Set the protocol fees", async () => {
+    let tx = new Transaction();
+    tx.instructions.push(createAssociatedTokenAccountIdempotentInstruction(payer, usdcTreasuryVault, treasury, usdcMint));
+    tx.instructions.push(createAssociatedTokenAccountIdempotentInstruction(payer, cbBtcTreasuryVault, treasury, cbBtcMint));
+    await program.provider.sendAndConfirm(tx, [payerKeypair], { skipPreflight: true });
+
+    await program.methods.updateConfig(null, { deposit: false, looping: false, repay: false, withdraw: false }, borrowFeeBps, 0, platformFeeBps, compoundTipBps)
+    .accountsStrict({
+      admin: program.provider.publicKey,
+      config,
+    })
+    .rpc();
+
+    await program.methods.setMaxRewardSlippage(slippageBps)
+    .accountsStrict({
+      admin: program.provider.publicKey,
+      config,
+    })
+    .rpc();
+  });
+
   let lookupTable: PublicKey;
 
   it("Create a lookup table", async () => {
@@ -191,9 +396,13 @@ describe("anchor-looping", () => {
 
   it("Looping", async () => {
     const amount = 100_000_000;
-    const swapResult = await swap(usdcMint, cbBtcMint, amount, 50, false, false, protocolAuthority, program.provider.connection);
-    const remainingAccounts = extractRemainingAccountsForSwap(swapResult.swapInstruction).remainingAccounts;
-    const flag = await hasCollateralOrBorrows(program.provider.connection, obligation);
+    // The borrow fee goes to the treasury, the route swaps what is left
+    const fee = Math.floor(amount * borrowFeeBps / 10_000);
+    const swapResult = await swap(usdcMint, cbBtcMint, amount - fee, slippageBps, false, false, protocolAuthority, program.provider.connection, platformFeeBps, cbBtcTreasuryVault);
+    const remainingAccounts = [
+      ...await obligationReserveAccounts(program.provider.connection, obligation),
+      ...extractRemainingAccountsForSwap(swapResult.swapInstruction).remainingAccounts,
+    ];
 
     const setComputeUnitLImitIx = ComputeBudgetProgram.setComputeUnitLimit({
       units: 1_200_000,
@@ -202,17 +411,25 @@ describe("anchor-looping", () => {
     const createUsdcVaultIx = createAssociatedTokenAccountIdempotentInstruction(payer, usdcVault, protocolAuthority, usdcMint);
 
     const loopingTx = await program.methods.looping(
-      flag,
       swapResult.swapInstruction.data,
+      slippageBps,
       new anchor.BN(amount)
     )
     .accountsStrict({
       payer,
+      authority: payer,
+      owner: payer,
       protocolAuthority,
+      position,
+      config,
+      marketConfig,
       inputMint: usdcMint,
       inputVault: usdcVault,
       outputMint: cbBtcMint,
       outputVault: cbBtcVault,
+      treasury,
+      treasuryVault: usdcTreasuryVault,
+      platformFeeVault: cbBtcTreasuryVault,
       instructionSysvarAccount: SYSVAR_INSTRUCTIONS_PUBKEY,
       tokenProgram: TOKEN_PROGRAM_ID,
       userMetadata,
@@ -226,13 +443,16 @@ describe("anchor-looping", () => {
       reserveBorrow: USDC_RESERVE,
       borrowReserveSourceLiquidity: USDC_SUPPLY_VAULT,
       borrowReserveLiquidityFeeReceiver: USDC_FEE_RECEIVER,
+      referrerTokenState: usdcReferrerTokenState,
       scopeOracle: SCOPE_ORACLE_ACCOUNT,
       obligationFarmState,
       reserveFarmState,
       kaminoLendingProgram: K_LEND_PROGRAM_ID,
       farmsProgram: K_FARMS_PROGRAM_ID,
-      eventAuthority: jupiterEventAuthority,
-      jupiterProgram: jupiterProgramId
+      jupiterEventAuthority: jupiterEventAuthority,
+      jupiterProgram: jupiterProgramId,
+      eventAuthority,
+      program: program.programId,
     })
     .remainingAccounts(remainingAccounts)
     .instruction();
@@ -257,10 +477,450 @@ describe("anchor-looping", () => {
     await program.provider.connection.sendTransaction(tx, {skipPreflight: true});
   });
 
-  it("Repay", async () => {
+  // Simulate a looping of `amount` USDC, for the tests that expect the program to reject it. With a
+  // `platformFeeVault` the route carries a platform fee, quoted into the cbBTC treasury vault
+  const simulateLooping = async (amount: number, platformFeeVault: PublicKey | null = null) => {
+    const fee = Math.floor(amount * borrowFeeBps / 10_000);
+    const swapResult = platformFeeVault
+      ? await swap(usdcMint, cbBtcMint, amount - fee, slippageBps, false, false, protocolAuthority, program.provider.connection, platformFeeBps, cbBtcTreasuryVault)
+      : await swap(usdcMint, cbBtcMint, amount - fee, slippageBps, false, false, protocolAuthority, program.provider.connection);
+    const remainingAccounts = [
+      ...await obligationReserveAccounts(program.provider.connection, obligation),
+      ...extractRemainingAccountsForSwap(swapResult.swapInstruction).remainingAccounts,
+    ];
+
+    const loopingIx = await program.methods.looping(
+      swapResult.swapInstruction.data,
+      slippageBps,
+      new anchor.BN(amount)
+    )
+    .accountsStrict({
+      payer,
+      authority: payer,
+      owner: payer,
+      protocolAuthority,
+      position,
+      config,
+      marketConfig,
+      inputMint: usdcMint,
+      inputVault: usdcVault,
+      outputMint: cbBtcMint,
+      outputVault: cbBtcVault,
+      treasury,
+      treasuryVault: usdcTreasuryVault,
+      platformFeeVault,
+      instructionSysvarAccount: SYSVAR_INSTRUCTIONS_PUBKEY,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      userMetadata,
+      obligation,
+      lendingMarket: LENDING_MARKET,
+      lendingMarketAuthority: LENDING_MARKET_AUTH,
+      reserveCollateral: CBBTC_RESERVE,
+      reserveLiquiditySupply: CBBTC_SUPPLY_VAULT,
+      reserveCollateralMint: CBBTC_COLLATERAL_MINT,
+      reserveDestinationDepositCollateral: CBBTC_COLLATERAL_VAULT,
+      reserveBorrow: USDC_RESERVE,
+      borrowReserveSourceLiquidity: USDC_SUPPLY_VAULT,
+      borrowReserveLiquidityFeeReceiver: USDC_FEE_RECEIVER,
+      referrerTokenState: usdcReferrerTokenState,
+      scopeOracle: SCOPE_ORACLE_ACCOUNT,
+      obligationFarmState,
+      reserveFarmState,
+      kaminoLendingProgram: K_LEND_PROGRAM_ID,
+      farmsProgram: K_FARMS_PROGRAM_ID,
+      jupiterEventAuthority: jupiterEventAuthority,
+      jupiterProgram: jupiterProgramId,
+      eventAuthority,
+      program: program.programId,
+    })
+    .remainingAccounts(remainingAccounts)
+    .instruction();
+
+    const addressLookupTableAccounts: AddressLookupTableAccount[] = [];
+    addressLookupTableAccounts.push((await program.provider.connection.getAddressLookupTable(lookupTable)).value);
+    addressLookupTableAccounts.push(...swapResult.addressLookupTableAccounts);
+
+    const messageV0 = new TransactionMessage({
+      payerKey: payer,
+      recentBlockhash: (await program.provider.connection.getLatestBlockhash()).blockhash,
+      instructions: [
+        ComputeBudgetProgram.setComputeUnitLimit({ units: 1_200_000 }),
+        loopingIx,
+      ],
+    }).compileToV0Message(addressLookupTableAccounts)
+
+    const tx = new VersionedTransaction(messageV0);
+    tx.sign([payerKeypair]);
+
+    return (await program.provider.connection.simulateTransaction(tx)).value;
+  };
+
+  it("Reject a swap priced too far from the oracle", async () => {
+    // No deviation allowed at all: the route fees alone put the swap under the oracle price
+    await program.methods.updateMarketConfig(0, 8_000)
+    .accountsStrict({
+      admin: program.provider.publicKey,
+      config,
+      marketConfig,
+    })
+    .rpc();
+
+    const simulation = await simulateLooping(10_000_000);
+    expect(simulation.err).to.not.be.null;
+    expect(simulation.logs.join("\n")).to.include("Error Code: PriceDeviationTooHigh");
+
+    await program.methods.updateMarketConfig(100, 8_000)
+    .accountsStrict({
+      admin: program.provider.publicKey,
+      config,
+      marketConfig,
+    })
+    .rpc();
+  });
+
+  it("Reject an exact-in platform fee paid in the input mint", async () => {
+    // Jupiter takes the platform fee of exact-in routes in the output mint, cbBTC here
+    const simulation = await simulateLooping(10_000_000, usdcTreasuryVault);
+    expect(simulation.err).to.not.be.null;
+    expect(simulation.logs.join("\n")).to.include("Error Code: PlatformFeeMintMismatch");
+  });
+
+  it("Reject a looping past the market max LTV", async () => {
+    // A 0.01% max LTV: any borrow leaves the position above it
+    await program.methods.updateMarketConfig(100, 1)
+    .accountsStrict({
+      admin: program.provider.publicKey,
+      config,
+      marketConfig,
+    })
+    .rpc();
+
+    const simulation = await simulateLooping(10_000_000);
+    expect(simulation.err).to.not.be.null;
+    expect(simulation.logs.join("\n")).to.include("Error Code: HealthFactorTooLow");
+
+    await program.methods.updateMarketConfig(100, 8_000)
+    .accountsStrict({
+      admin: program.provider.publicKey,
+      config,
+      marketConfig,
+    })
+    .rpc();
+  });
+
+  it("Reject market config limits out of bounds", async () => {
+    for (const [maxPriceDeviationBps, maxLtvBps] of [[100, 0], [100, 10_001], [10_001, 8_000]]) {
+      const tx = await program.methods.updateMarketConfig(maxPriceDeviationBps, maxLtvBps)
+      .accountsStrict({
+        admin: program.provider.publicKey,
+        config,
+        marketConfig,
+      })
+      .transaction();
+      tx.feePayer = payer;
+      tx.recentBlockhash = (await program.provider.connection.getLatestBlockhash()).blockhash;
+
+      const simulation = await program.provider.connection.simulateTransaction(tx, [payerKeypair]);
+      expect(simulation.value.err).to.not.be.null;
+      expect(simulation.value.logs.join("\n")).to.include("Error Code: InvalidMarketConfig");
+    }
+  });
+
+  it("Reject a swap slippage above the reserve pair max", async () => {
+    const amount = 1_000_000;
+    const tooHighSlippageBps = 200;
+    const swapResult = await swap(usdcMint, cbBtcMint, amount, tooHighSlippageBps, false, false, protocolAuthority, program.provider.connection);
+    const remainingAccounts = [
+      ...await obligationReserveAccounts(program.provider.connection, obligation),
+      ...extractRemainingAccountsForSwap(swapResult.swapInstruction).remainingAccounts,
+    ];
+
+    const loopingIx = await program.methods.looping(
+      swapResult.swapInstruction.data,
+      tooHighSlippageBps,
+      new anchor.BN(amount)
+    )
+    .accountsStrict({
+      payer,
+      authority: payer,
+      owner: payer,
+      protocolAuthority,
+      position,
+      config,
+      marketConfig,
+      inputMint: usdcMint,
+      inputVault: usdcVault,
+      outputMint: cbBtcMint,
+      outputVault: cbBtcVault,
+      treasury,
+      treasuryVault: usdcTreasuryVault,
+      platformFeeVault: null,
+      instructionSysvarAccount: SYSVAR_INSTRUCTIONS_PUBKEY,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      userMetadata,
+      obligation,
+      lendingMarket: LENDING_MARKET,
+      lendingMarketAuthority: LENDING_MARKET_AUTH,
+      reserveCollateral: CBBTC_RESERVE,
+      reserveLiquiditySupply: CBBTC_SUPPLY_VAULT,
+      reserveCollateralMint: CBBTC_COLLATERAL_MINT,
+      reserveDestinationDepositCollateral: CBBTC_COLLATERAL_VAULT,
+      reserveBorrow: USDC_RESERVE,
+      borrowReserveSourceLiquidity: USDC_SUPPLY_VAULT,
+      borrowReserveLiquidityFeeReceiver: USDC_FEE_RECEIVER,
+      referrerTokenState: usdcReferrerTokenState,
+      scopeOracle: SCOPE_ORACLE_ACCOUNT,
+      obligationFarmState,
+      reserveFarmState,
+      kaminoLendingProgram: K_LEND_PROGRAM_ID,
+      farmsProgram: K_FARMS_PROGRAM_ID,
+      jupiterEventAuthority: jupiterEventAuthority,
+      jupiterProgram: jupiterProgramId,
+      eventAuthority,
+      program: program.programId,
+    })
+    .remainingAccounts(remainingAccounts)
+    .instruction();
+
+    const addressLookupTableAccounts: AddressLookupTableAccount[] = [];
+    addressLookupTableAccounts.push((await program.provider.connection.getAddressLookupTable(lookupTable)).value);
+    addressLookupTableAccounts.push(...swapResult.addressLookupTableAccounts);
+
+    const messageV0 = new TransactionMessage({
+      payerKey: payer,
+      recentBlockhash: (await program.provider.connection.getLatestBlockhash()).blockhash,
+      instructions: [
+        ComputeBudgetProgram.setComputeUnitLimit({ units: 1_200_000 }),
+        loopingIx,
+      ],
+    }).compileToV0Message(addressLookupTableAccounts)
+
+    const tx = new VersionedTransaction(messageV0);
+    tx.sign([payerKeypair]);
+
+    const simulation = await program.provider.connection.simulateTransaction(tx);
+    expect(simulation.value.err).to.not.be.null;
+    expect(simulation.value.logs.join("\n")).to.include("Error Code: SlippageTooHigh");
+  });
+
+  it("Leverage", async () => {
+    // Aim for the leverage that roughly 100 USDC more of debt gives, the program derives the exact borrow itself
+    const amount = new anchor.BN(100_000_000);
+    const { depositedValueSf, borrowedValueSf } = await obligationValues(program.provider.connection, obligation);
+    const extraValueSf = new anchor.BN(100).shln(60);
+    const targetLeverageBps = depositedValueSf.add(extraValueSf).muln(10_000).div(depositedValueSf.sub(borrowedValueSf)).toNumber();
+
+    // The flash loan covers the derived borrow with some room, what isn't used goes straight back
+    const flashAmount = amount.muln(11).divn(10);
+    const swapResult = await swap(usdcMint, cbBtcMint, amount.toNumber(), slippageBps, false, false, protocolAuthority, program.provider.connection);
+    const remainingAccounts = [
+      ...await obligationReserveAccounts(program.provider.connection, obligation),
+      ...extractRemainingAccountsForSwap(swapResult.swapInstruction).remainingAccounts,
+    ];
+    const payerUsdcAccount = getAssociatedTokenAddressSync(usdcMint, payer);
+    const positionBefore = await program.account.position.fetch(position);
+    const { ltvBps: ltvBpsBefore } = await obligationValues(program.provider.connection, obligation);
+
+    // The flash loan fee is paid from the payer USDC account
+    await surfnetTokenAirdrop(program.provider.connection, payer.toString(), usdcMint.toString(), 1_000_000);
+
+    const leverageIx = await program.methods.leverage(
+      swapResult.swapInstruction.data,
+      slippageBps,
+      targetLeverageBps
+    )
+    .accountsStrict({
+      payer,
+      owner: payer,
+      protocolAuthority,
+      position,
+      config,
+      marketConfig,
+      inputMint: usdcMint,
+      inputVault: usdcVault,
+      ownerDebtAccount: payerUsdcAccount,
+      outputMint: cbBtcMint,
+      outputVault: cbBtcVault,
+      treasury,
+      treasuryVault: usdcTreasuryVault,
+      platformFeeVault: null,
+      instructionSysvarAccount: SYSVAR_INSTRUCTIONS_PUBKEY,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      userMetadata,
+      obligation,
+      lendingMarket: LENDING_MARKET,
+      lendingMarketAuthority: LENDING_MARKET_AUTH,
+      reserveCollateral: CBBTC_RESERVE,
+      reserveLiquiditySupply: CBBTC_SUPPLY_VAULT,
+      reserveCollateralMint: CBBTC_COLLATERAL_MINT,
+      reserveDestinationDepositCollateral: CBBTC_COLLATERAL_VAULT,
+      reserveBorrow: USDC_RESERVE,
+      borrowReserveSourceLiquidity: USDC_SUPPLY_VAULT,
+      borrowReserveLiquidityFeeReceiver: USDC_FEE_RECEIVER,
+      referrerTokenState: usdcReferrerTokenState,
+      scopeOracle: SCOPE_ORACLE_ACCOUNT,
+      obligationFarmState,
+      reserveFarmState,
+      kaminoLendingProgram: K_LEND_PROGRAM_ID,
+      farmsProgram: K_FARMS_PROGRAM_ID,
+      jupiterEventAuthority: jupiterEventAuthority,
+      jupiterProgram: jupiterProgramId,
+      eventAuthority,
+      program: program.programId,
+    })
+    .remainingAccounts(remainingAccounts)
+    .instruction();
+
+    const addressLookupTableAccounts: AddressLookupTableAccount[] = [];
+    addressLookupTableAccounts.push((await program.provider.connection.getAddressLookupTable(lookupTable)).value);
+    addressLookupTableAccounts.push(...swapResult.addressLookupTableAccounts);
+
+    // The flash borrow has to sit right before the leverage instruction and the flash repay right after it
+    const messageV0 = new TransactionMessage({
+      payerKey: payer,
+      recentBlockhash: (await program.provider.connection.getLatestBlockhash()).blockhash,
+      instructions: [
+        ComputeBudgetProgram.setComputeUnitLimit({ units: 1_400_000 }),
+        flashBorrowUsdcIx(payer, payerUsdcAccount, flashAmount),
+        leverageIx,
+        flashRepayUsdcIx(payer, payerUsdcAccount, flashAmount, 1),
+      ],
+    }).compileToV0Message(addressLookupTableAccounts)
+
+    const tx = new VersionedTransaction(messageV0);
+    tx.sign([payerKeypair]);
+
+    const signature = await program.provider.connection.sendTransaction(tx, {skipPreflight: true});
+    const confirmation = await program.provider.connection.confirmTransaction(signature, "confirmed");
+    expect(confirmation.value.err).to.be.null;
+
+    // More collateral and more debt, the debt carrying the borrow fee on top of the swapped amount
+    const positionAfter = await program.account.position.fetch(position);
+    const deposited = positionAfter.deposited.sub(positionBefore.deposited);
+    const borrowed = positionAfter.borrowed.sub(positionBefore.borrowed);
+    expect(deposited.gtn(0)).to.be.true;
+    expect(borrowed.gtn(0)).to.be.true;
+    expect(borrowed.lte(flashAmount.add(flashAmount.muln(borrowFeeBps).divn(10_000)))).to.be.true;
+    expect(positionAfter.repaid.eq(positionBefore.repaid)).to.be.true;
+
+    const { ltvBps: ltvBpsAfter } = await obligationValues(program.provider.connection, obligation);
+    expect(ltvBpsAfter).to.be.greaterThan(ltvBpsBefore);
+    expect(ltvBpsAfter).to.be.at.most(8_000);
+  });
+
+  // Deleverage through a Kamino flash loan of `amount` USDC, swapping the collateral back with an exact-out
+  // route delivering `outAmount`. Returns the position and the obligation values before and after.
+  const deleverage = async (amount: anchor.BN, outAmount: anchor.BN, repayAll: boolean) => {
+    const swapResult = await swap(cbBtcMint, usdcMint, outAmount.toNumber(), slippageBps, true, false, protocolAuthority, program.provider.connection);
+    const remainingAccounts = [
+      ...await obligationReserveAccounts(program.provider.connection, obligation),
+      ...extractRemainingAccountsForSwap(swapResult.swapInstruction).remainingAccounts,
+    ];
+    const payerUsdcAccount = getAssociatedTokenAddressSync(usdcMint, payer);
+    const positionBefore = await program.account.position.fetch(position);
+    const valuesBefore = await obligationValues(program.provider.connection, obligation);
+
+    // Withdraw the most the exact-out route can spend, what it leaves gets deposited back
+    const deleverageIx = await program.methods.deleverage(
+      swapResult.swapInstruction.data,
+      slippageBps,
+      amount,
+      new anchor.BN(Number(swapResult.quoteResponse.otherAmountThreshold)),
+      repayAll
+    ).accountsStrict({
+      payer,
+      owner: payer,
+      protocolAuthority,
+      position,
+      config,
+      marketConfig,
+      inputMint: cbBtcMint,
+      inputVault: cbBtcVault,
+      outputMint: usdcMint,
+      outputVault: usdcVault,
+      ownerDebtAccount: payerUsdcAccount,
+      treasury,
+      treasuryVault: usdcTreasuryVault,
+      instructionSysvarAccount: SYSVAR_INSTRUCTIONS_PUBKEY,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      userMetadata,
+      obligation,
+      lendingMarket: LENDING_MARKET,
+      lendingMarketAuthority: LENDING_MARKET_AUTH,
+      reserveCollateral: CBBTC_RESERVE,
+      reserveLiquiditySupply: CBBTC_SUPPLY_VAULT,
+      reserveCollateralMint: CBBTC_COLLATERAL_MINT,
+      reserveSourceCollateral: CBBTC_COLLATERAL_VAULT,
+      reserveBorrow: USDC_RESERVE,
+      borrowReserveDestinationLiquidity: USDC_SUPPLY_VAULT,
+      scopeOracle: SCOPE_ORACLE_ACCOUNT,
+      obligationFarmState,
+      reserveFarmState,
+      kaminoLendingProgram: K_LEND_PROGRAM_ID,
+      farmsProgram: K_FARMS_PROGRAM_ID,
+      jupiterEventAuthority: jupiterEventAuthority,
+      jupiterProgram: jupiterProgramId,
+      eventAuthority,
+      program: program.programId,
+    })
+    .remainingAccounts(remainingAccounts)
+    .instruction();
+
+    const addressLookupTableAccounts: AddressLookupTableAccount[] = [];
+    addressLookupTableAccounts.push((await program.provider.connection.getAddressLookupTable(lookupTable)).value);
+    addressLookupTableAccounts.push(...swapResult.addressLookupTableAccounts);
+
+    // The flash borrow has to sit right before the deleverage instruction and the flash repay right after it
+    const messageV0 = new TransactionMessage({
+      payerKey: payer,
+      recentBlockhash: (await program.provider.connection.getLatestBlockhash()).blockhash,
+      instructions: [
+        ComputeBudgetProgram.setComputeUnitLimit({ units: 1_400_000 }),
+        flashBorrowUsdcIx(payer, payerUsdcAccount, amount),
+        deleverageIx,
+        flashRepayUsdcIx(payer, payerUsdcAccount, amount, 1),
+      ],
+    }).compileToV0Message(addressLookupTableAccounts)
+
+    const tx = new VersionedTransaction(messageV0);
+    tx.sign([payerKeypair]);
+
+    const signature = await program.provider.connection.sendTransaction(tx, {skipPreflight: true});
+    const confirmation = await program.provider.connection.confirmTransaction(signature, "confirmed");
+    expect(confirmation.value.err).to.be.null;
+
+    return {
+      positionBefore,
+      positionAfter: await program.account.position.fetch(position),
+      valuesBefore,
+      valuesAfter: await obligationValues(program.provider.connection, obligation),
+    };
+  };
+
+  it("Deleverage", async () => {
+    // Flash borrow half of the debt to bring the LTV down in one go, "Repay" and the full deleverage clear the rest
     const repayAmount = await calcuateRepaymentAmount(program.provider.connection, obligation);
-    const swapResult = await swap(cbBtcMint, usdcMint, repayAmount.toNumber(), 50, true, false, protocolAuthority, program.provider.connection);
-    const remainingAccounts = extractRemainingAccountsForSwap(swapResult.swapInstruction).remainingAccounts;
+    const amount = new anchor.BN(repayAmount.toNumber()).divn(2);
+
+    const { positionBefore, positionAfter, valuesBefore, valuesAfter } = await deleverage(amount, amount, false);
+
+    // The flash loan went into the debt, paid for by collateral
+    expect(positionAfter.repaid.sub(positionBefore.repaid).eq(amount)).to.be.true;
+    expect(positionAfter.withdrawn.gt(positionBefore.withdrawn)).to.be.true;
+    expect(positionAfter.borrowed.eq(positionBefore.borrowed)).to.be.true;
+    expect(valuesAfter.borrowedValueSf.lt(valuesBefore.borrowedValueSf)).to.be.true;
+    expect(valuesAfter.depositedValueSf.lt(valuesBefore.depositedValueSf)).to.be.true;
+    expect(valuesAfter.ltvBps).to.be.lessThan(valuesBefore.ltvBps);
+  });
+
+  // Repay `repayAmount` USDC through an exact-out route carrying a platform fee, quoted into the cbBTC
+  // treasury vault while `platformFeeVault` is the one handed to the program
+  const repayTransaction = async (repayAmount: anchor.BN, platformFeeVault: PublicKey) => {
+    const swapResult = await swap(cbBtcMint, usdcMint, repayAmount.toNumber(), slippageBps, true, false, protocolAuthority, program.provider.connection, platformFeeBps, cbBtcTreasuryVault);
+    const remainingAccounts = [
+      ...await obligationReserveAccounts(program.provider.connection, obligation),
+      ...extractRemainingAccountsForSwap(swapResult.swapInstruction).remainingAccounts,
+    ];
 
     const setComputeUnitLImitIx = ComputeBudgetProgram.setComputeUnitLimit({
       units: 1_200_000,
@@ -268,15 +928,24 @@ describe("anchor-looping", () => {
 
     const repayTx = await program.methods.repay(
       swapResult.swapInstruction.data,
+      slippageBps,
       new anchor.BN(Number(swapResult.quoteResponse.inAmount)),
       new anchor.BN(repayAmount.toNumber())
     ).accountsStrict({
       payer,
+      authority: payer,
+      owner: payer,
       protocolAuthority,
+      position,
+      config,
+      marketConfig,
       inputMint: cbBtcMint,
       inputVault: cbBtcVault,
       outputMint: usdcMint,
       outputVault: usdcVault,
+      treasury,
+      treasuryVault: usdcTreasuryVault,
+      platformFeeVault,
       instructionSysvarAccount: SYSVAR_INSTRUCTIONS_PUBKEY,
       tokenProgram: TOKEN_PROGRAM_ID,
       userMetadata,
@@ -294,8 +963,10 @@ describe("anchor-looping", () => {
       reserveFarmState,
       kaminoLendingProgram: K_LEND_PROGRAM_ID,
       farmsProgram: K_FARMS_PROGRAM_ID,
-      eventAuthority: jupiterEventAuthority,
-      jupiterProgram: jupiterProgramId
+      jupiterEventAuthority: jupiterEventAuthority,
+      jupiterProgram: jupiterProgramId,
+      eventAuthority,
+      program: program.programId,
     })
     .remainingAccounts(remainingAccounts)
     .instruction();
@@ -316,6 +987,325 @@ describe("anchor-looping", () => {
     const tx = new VersionedTransaction(messageV0);
     tx.sign([payerKeypair]);
 
+    return tx;
+  };
+
+  it("Reject an exact-out platform fee paid in the output mint", async () => {
+    // Jupiter takes the platform fee of exact-out routes in the input mint, cbBTC here
+    const repayAmount = (await calcuateRepaymentAmount(program.provider.connection, obligation)).div(2).floor();
+    const tx = await repayTransaction(repayAmount, usdcTreasuryVault);
+
+    const simulation = (await program.provider.connection.simulateTransaction(tx)).value;
+    expect(simulation.err).to.not.be.null;
+    expect(simulation.logs.join("\n")).to.include("Error Code: PlatformFeeMintMismatch");
+  });
+
+  it("Repay", async () => {
+    // Half of what is left, "Deleverage the whole debt" clears the rest
+    const repayAmount = (await calcuateRepaymentAmount(program.provider.connection, obligation)).div(2).floor();
+    const tx = await repayTransaction(repayAmount, cbBtcTreasuryVault);
+
     await program.provider.connection.sendTransaction(tx, {skipPreflight: true});
   });
+
+  it("Deleverage the whole debt", async () => {
+    // The interest accrued up to the slot is unknown here: flash borrow and swap back a bit more than the
+    // debt, Kamino caps the repay and the surplus stays with the owner
+    const repayAmount = await calcuateRepaymentAmount(program.provider.connection, obligation);
+    const amount = new anchor.BN(repayAmount.mul(1.001).toDecimalPlaces(0).toNumber());
+
+    const { positionBefore, positionAfter, valuesBefore, valuesAfter } = await deleverage(amount, amount, true);
+
+    const repaid = positionAfter.repaid.sub(positionBefore.repaid);
+    expect(repaid.gtn(0)).to.be.true;
+    expect(repaid.lte(amount)).to.be.true;
+    expect(valuesBefore.borrowedValueSf.gtn(0)).to.be.true;
+    expect(valuesAfter.borrowedValueSf.isZero()).to.be.true;
+    expect(valuesAfter.ltvBps).to.equal(0);
+  });
+
+  it("Withdraw", async () => {
+    const userDestinationLiquidity = getAssociatedTokenAddressSync(cbBtcMint, payer);
+
+    await program.methods.withdraw(
+      new anchor.BN(1_000_000)
+    ).accountsStrict({
+      payer,
+      owner: payer,
+      protocolAuthority,
+      position,
+      config,
+      marketConfig,
+      reserveLiquidityMint: cbBtcMint,
+      vault: cbBtcVault,
+      userDestinationLiquidity,
+      instructionSysvarAccount: SYSVAR_INSTRUCTIONS_PUBKEY,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      obligation,
+      lendingMarket: LENDING_MARKET,
+      lendingMarketAuthority: LENDING_MARKET_AUTH,
+      reserveCollateral: CBBTC_RESERVE,
+      reserveBorrow: USDC_RESERVE,
+      reserveLiquiditySupply: CBBTC_SUPPLY_VAULT,
+      reserveCollateralMint: CBBTC_COLLATERAL_MINT,
+      reserveSourceCollateral: CBBTC_COLLATERAL_VAULT,
+      scopeOracle: SCOPE_ORACLE_ACCOUNT,
+      obligationFarmState,
+      reserveFarmState,
+      kaminoLendingProgram: K_LEND_PROGRAM_ID,
+      farmsProgram: K_FARMS_PROGRAM_ID,
+      eventAuthority,
+      program: program.programId,
+    })
+    .remainingAccounts(await obligationReserveAccounts(program.provider.connection, obligation))
+    .preInstructions([
+      ComputeBudgetProgram.setComputeUnitLimit({ units: 600_000 }),
+    ])
+    .signers([payerKeypair])
+    .rpc({ skipPreflight: true });
+  });
+
+  it("Compound the collateral farm rewards", async () => {
+    const rewardIndex = 0;
+    const { globalConfig, rewardMint, rewardsVault, rewardsTreasuryVault, farmVaultsAuthority } = await farmRewardAccounts(program.provider.connection, reserveFarmState, rewardIndex);
+    const rewardVault = getAssociatedTokenAddressSync(rewardMint, protocolAuthority, true);
+    const keeperCollateralAccount = getAssociatedTokenAddressSync(cbBtcMint, payer);
+
+    // Seed the reward vault so the swapped amount doesn't depend on what the farm accrued
+    await surfnetTokenAirdrop(program.provider.connection, protocolAuthority.toString(), rewardMint.toString(), 10_000_000);
+    const amount = 5_000_000;
+    const swapResult = await swap(rewardMint, cbBtcMint, amount, slippageBps, false, false, protocolAuthority, program.provider.connection, platformFeeBps, cbBtcTreasuryVault);
+    const remainingAccounts = [
+      ...await obligationReserveAccounts(program.provider.connection, obligation),
+      ...extractRemainingAccountsForSwap(swapResult.swapInstruction).remainingAccounts,
+    ];
+    // The tip is taken on the collateral the swap delivers, at least the quote minimum
+    const minimumTip = Math.floor(Number(swapResult.quoteResponse.otherAmountThreshold) * compoundTipBps / 10_000);
+    const keeperBalanceBefore = Number((await program.provider.connection.getTokenAccountBalance(keeperCollateralAccount).catch(() => ({ value: { amount: "0" } }))).value.amount);
+
+    // The owner compounds as keeper, so no reward reserve is needed to price the swap
+    const compoundIx = await program.methods.compound(
+      swapResult.swapInstruction.data,
+      slippageBps,
+      new anchor.BN(amount),
+      new anchor.BN(rewardIndex)
+    )
+    .accountsStrict({
+      keeper: payer,
+      owner: payer,
+      protocolAuthority,
+      position,
+      config,
+      marketConfig,
+      rewardMint,
+      rewardVault,
+      collateralMint: cbBtcMint,
+      collateralVault: cbBtcVault,
+      keeperCollateralAccount,
+      treasury,
+      platformFeeVault: cbBtcTreasuryVault,
+      instructionSysvarAccount: SYSVAR_INSTRUCTIONS_PUBKEY,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+      systemProgram: SystemProgram.programId,
+      obligation,
+      lendingMarket: LENDING_MARKET,
+      lendingMarketAuthority: LENDING_MARKET_AUTH,
+      reserveCollateral: CBBTC_RESERVE,
+      reserveBorrow: USDC_RESERVE,
+      reserveReward: null,
+      reserveLiquiditySupply: CBBTC_SUPPLY_VAULT,
+      reserveCollateralMint: CBBTC_COLLATERAL_MINT,
+      reserveDestinationDepositCollateral: CBBTC_COLLATERAL_VAULT,
+      scopeOracle: SCOPE_ORACLE_ACCOUNT,
+      obligationFarmState,
+      reserveFarmState,
+      kaminoLendingProgram: K_LEND_PROGRAM_ID,
+      farmsProgram: K_FARMS_PROGRAM_ID,
+      globalConfig,
+      rewardsVault,
+      rewardsTreasuryVault,
+      farmVaultsAuthority,
+      scopePrices: null,
+      jupiterEventAuthority: jupiterEventAuthority,
+      jupiterProgram: jupiterProgramId,
+      eventAuthority,
+      program: program.programId,
+    })
+    .remainingAccounts(remainingAccounts)
+    .instruction();
+
+    const addressLookupTableAccounts: AddressLookupTableAccount[] = [];
+    addressLookupTableAccounts.push((await program.provider.connection.getAddressLookupTable(lookupTable)).value);
+    addressLookupTableAccounts.push(...swapResult.addressLookupTableAccounts);
+
+    const messageV0 = new TransactionMessage({
+      payerKey: payer,
+      recentBlockhash: (await program.provider.connection.getLatestBlockhash()).blockhash,
+      instructions: [
+        ComputeBudgetProgram.setComputeUnitLimit({ units: 1_400_000 }),
+        createAssociatedTokenAccountIdempotentInstruction(payer, keeperCollateralAccount, payer, cbBtcMint),
+        compoundIx,
+      ],
+    }).compileToV0Message(addressLookupTableAccounts)
+
+    const tx = new VersionedTransaction(messageV0);
+    tx.sign([payerKeypair]);
+
+    const signature = await program.provider.connection.sendTransaction(tx, {skipPreflight: true});
+    await program.provider.connection.confirmTransaction(signature, "confirmed");
+
+    const keeperBalanceAfter = await program.provider.connection.getTokenAccountBalance(keeperCollateralAccount);
+    expect(Number(keeperBalanceAfter.value.amount) - keeperBalanceBefore).to.be.at.least(minimumTip);
+  });
+
+  it("Harvest the collateral farm rewards", async () => {
+    // Reward mint, reward vault, user destination reward, rewards vault and rewards treasury vault of every reward index
+    const rewardCount = await farmRewardCount(program.provider.connection, reserveFarmState);
+    const rewards = await Promise.all([...Array(rewardCount).keys()].map(async (rewardIndex) => {
+      const { rewardMint, rewardsVault, rewardsTreasuryVault } = await farmRewardAccounts(program.provider.connection, reserveFarmState, rewardIndex);
+      return {
+        rewardMint,
+        rewardVault: getAssociatedTokenAddressSync(rewardMint, protocolAuthority, true),
+        userDestinationReward: getAssociatedTokenAddressSync(rewardMint, payer),
+        rewardsVault,
+        rewardsTreasuryVault,
+      };
+    }));
+    const { globalConfig, farmVaultsAuthority } = await farmRewardAccounts(program.provider.connection, reserveFarmState, 0);
+
+    const compoundedVault = rewards[0].rewardVault;
+    const vaultBalanceBefore = await program.provider.connection.getTokenAccountBalance(compoundedVault);
+
+    await program.methods.harvest()
+    .accountsStrict({
+      owner: payer,
+      protocolAuthority,
+      position,
+      config,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      obligationFarmState,
+      reserveFarmState,
+      globalConfig,
+      farmVaultsAuthority,
+      scopePrices: null,
+      farmsProgram: K_FARMS_PROGRAM_ID,
+      eventAuthority,
+      program: program.programId,
+    })
+    .remainingAccounts(rewards.flatMap(({ rewardMint, rewardVault, userDestinationReward, rewardsVault, rewardsTreasuryVault }) => [
+      { pubkey: rewardMint, isSigner: false, isWritable: false },
+      { pubkey: rewardVault, isSigner: false, isWritable: true },
+      { pubkey: userDestinationReward, isSigner: false, isWritable: true },
+      { pubkey: rewardsVault, isSigner: false, isWritable: true },
+      { pubkey: rewardsTreasuryVault, isSigner: false, isWritable: true },
+    ]))
+    .preInstructions(rewards.flatMap(({ rewardMint, rewardVault, userDestinationReward }) => [
+      createAssociatedTokenAccountIdempotentInstruction(payer, rewardVault, protocolAuthority, rewardMint),
+      createAssociatedTokenAccountIdempotentInstruction(payer, userDestinationReward, payer, rewardMint),
+    ]))
+    .signers([payerKeypair])
+    .rpc({ skipPreflight: true });
+
+    // Only the harvested rewards move, what compound left in the vault stays there
+    const vaultBalanceAfter = await program.provider.connection.getTokenAccountBalance(compoundedVault);
+    expect(vaultBalanceAfter.value.amount).to.equal(vaultBalanceBefore.value.amount);
+  });
+
+  it("Close Position", async () => {
+    await program.methods.closePosition(
+      Buffer.from([]),
+      0,
+      new anchor.BN(0),
+      new anchor.BN(0)
+    ).accountsStrict({
+      payer,
+      owner: payer,
+      protocolAuthority,
+      position,
+      config,
+      marketConfig,
+      collateralMint: cbBtcMint,
+      collateralVault: cbBtcVault,
+      ownerCollateralAccount: getAssociatedTokenAddressSync(cbBtcMint, payer),
+      debtMint: usdcMint,
+      debtVault: usdcVault,
+      ownerDebtAccount: getAssociatedTokenAddressSync(usdcMint, payer),
+      instructionSysvarAccount: SYSVAR_INSTRUCTIONS_PUBKEY,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      obligation,
+      lendingMarket: LENDING_MARKET,
+      lendingMarketAuthority: LENDING_MARKET_AUTH,
+      reserveCollateral: CBBTC_RESERVE,
+      reserveLiquiditySupply: CBBTC_SUPPLY_VAULT,
+      reserveCollateralMint: CBBTC_COLLATERAL_MINT,
+      reserveSourceCollateral: CBBTC_COLLATERAL_VAULT,
+      reserveBorrow: USDC_RESERVE,
+      borrowReserveDestinationLiquidity: USDC_SUPPLY_VAULT,
+      scopeOracle: SCOPE_ORACLE_ACCOUNT,
+      obligationFarmState,
+      reserveFarmState,
+      kaminoLendingProgram: K_LEND_PROGRAM_ID,
+      farmsProgram: K_FARMS_PROGRAM_ID,
+      jupiterEventAuthority: jupiterEventAuthority,
+      jupiterProgram: jupiterProgramId,
+      eventAuthority,
+      program: program.programId,
+    })
+    .remainingAccounts(await obligationReserveAccounts(program.provider.connection, obligation))
+    .preInstructions([
+      ComputeBudgetProgram.setComputeUnitLimit({ units: 1_200_000 }),
+      createAssociatedTokenAccountIdempotentInstruction(payer, getAssociatedTokenAddressSync(usdcMint, payer), payer, usdcMint),
+    ])
+    .signers([payerKeypair])
+    .rpc({ skipPreflight: true });
+  });
+
+  it("Withdraw the referral fees", async () => {
+    await program.methods.withdrawReferrerFees()
+    .accountsStrict({
+      admin: program.provider.publicKey,
+      config,
+      referrer,
+      treasury,
+      reserveLiquidityMint: usdcMint,
+      referrerVault: getAssociatedTokenAddressSync(usdcMint, referrer, true),
+      treasuryVault: usdcTreasuryVault,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+      systemProgram: SystemProgram.programId,
+      referrerTokenState: usdcReferrerTokenState,
+      lendingMarket: LENDING_MARKET,
+      lendingMarketAuthority: LENDING_MARKET_AUTH,
+      reserve: USDC_RESERVE,
+      reserveSupplyLiquidity: USDC_SUPPLY_VAULT,
+      kaminoLendingProgram: K_LEND_PROGRAM_ID,
+    })
+    .rpc({ skipPreflight: true });
+  });
+
+  it("Withdraw the protocol fees", async () => {
+    const admin = program.provider.publicKey;
+    const destination = getAssociatedTokenAddressSync(usdcMint, admin);
+
+    await program.methods.withdrawFees(new anchor.BN("18446744073709551615"))
+    .accountsStrict({
+      admin,
+      config,
+      treasury,
+      mint: usdcMint,
+      treasuryVault: usdcTreasuryVault,
+      destination,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    })
+    .preInstructions([
+      createAssociatedTokenAccountIdempotentInstruction(admin, destination, admin, usdcMint),
+    ])
+    .rpc({ skipPreflight: true });
+
+    const treasuryBalance = await program.provider.connection.getTokenAccountBalance(usdcTreasuryVault);
+    const destinationBalance = await program.provider.connection.getTokenAccountBalance(destination);
+    expect(treasuryBalance.value.amount).to.equal("0");
+    expect(Number(destinationBalance.value.amount)).to.be.greaterThan(0);
+  });
 });
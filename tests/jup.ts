@@ -101,7 +101,9 @@ export const swap = async (
     exactOutRoute: boolean,
     onlyDirectRoutes: boolean,
     userPublicKey: PublicKey,
-    connection: Connection
+    connection: Connection,
+    platformFeeBps: number = 0,
+    feeAccount?: PublicKey
 ): Promise<{
     quoteResponse: any,
     swapInstruction: TransactionInstruction,
@@ -109,7 +111,7 @@ export const swap = async (
 }> => {
     const quoteResponse = await (
         await fetch(
-            `https://lite-api.jup.ag/swap/v1/quote?inputMint=${inputMint.toString()}&outputMint=${outputMint.toString()}&amount=${amount}&slippageBps=${slippageBps}&onlyDirectRoutes=${onlyDirectRoutes}&swapMode=${exactOutRoute ? "ExactOut" : "ExactIn"}&excludeDexes=ZeroFi,HumidiFi,Tessera`,
+            `https://lite-api.jup.ag/swap/v1/quote?inputMint=${inputMint.toString()}&outputMint=${outputMint.toString()}&amount=${amount}&slippageBps=${slippageBps}&onlyDirectRoutes=${onlyDirectRoutes}&swapMode=${exactOutRoute ? "ExactOut" : "ExactIn"}&platformFeeBps=${platformFeeBps}&excludeDexes=ZeroFi,HumidiFi,Tessera`,
         )
       ).json();
   
@@ -123,6 +125,7 @@ export const swap = async (
             quoteResponse,
             userPublicKey: userPublicKey.toString(),
             dynamicSlippage: true,
+            feeAccount: feeAccount?.toString(),
             })
         })
     ).json();
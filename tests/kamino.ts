@@ -1,10 +1,8 @@
-import { Connection, PublicKey } from "@solana/web3.js"
+import { AccountMeta, Connection, PublicKey, SYSVAR_INSTRUCTIONS_PUBKEY, TransactionInstruction } from "@solana/web3.js"
+import { TOKEN_PROGRAM_ID } from "@solana/spl-token";
 import { KaminoObligation, KaminoMarket } from "@kamino-finance/klend-sdk";
 import { Decimal } from "decimal.js";
-
-// Flags to indicate if obligation has collateral or borrows (matching constant.rs)
-export const FLAG_HAS_COLLATERAL = 1 << 0;
-export const FLAG_HAS_BORROWS = 1 << 1;
+import { BN } from "@coral-xyz/anchor";
 
 export const K_LEND_PROGRAM_ID = new PublicKey("KLend2g3cP87fffoy8q1mQqGKjrxjC8boSyAYavgmjD");
 export const K_FARMS_PROGRAM_ID = new PublicKey("FarmsPZpWu9i7Kky8tPN37rs2TpmMrAZrC7S7vJa91Hr");
@@ -49,6 +47,15 @@ export const userMetadataAccount = (
     )[0]
 }
 
+export const referrerTokenStateAccount = (
+    referrer: PublicKey,
+    reserve: PublicKey
+): PublicKey => {
+    return PublicKey.findProgramAddressSync(
+        [Buffer.from("referrer_acc"), referrer.toBuffer(), reserve.toBuffer()], K_LEND_PROGRAM_ID
+    )[0]
+}
+
 export const obligationFarmStatePdaAccount = (
     farm: PublicKey,
     obligation: PublicKey
@@ -58,34 +65,58 @@ export const obligationFarmStatePdaAccount = (
     )[0]
 }
 
-export async function hasCollateralOrBorrows(connection: Connection, obligationAddress: PublicKey): Promise<number> {
-    const lendingMarket = await KaminoMarket.load(connection, LENDING_MARKET, 400);
-    const kaminoObligation = await KaminoObligation.load(lendingMarket, obligationAddress);
-    
-    if (!kaminoObligation) {
-        return 255;
-    }
-    
-    // Get current total deposit value from Kamino's stats
-    const currentTotalDeposit = kaminoObligation.refreshedStats.userTotalDeposit;
-    
-    // Get current total borrow value from Kamino's stats
-    const currentTotalBorrow = kaminoObligation.refreshedStats.userTotalBorrowBorrowFactorAdjusted;
+// Accounts of one reward of a Kamino farm, read from the farm state (reward infos start at byte 192 and
+// are 712 bytes each, the mint and the rewards vault come first)
+export async function farmRewardAccounts(connection: Connection, farm: PublicKey, rewardIndex: number) {
+    const { data } = await connection.getAccountInfo(farm);
+    const rewardInfo = data.subarray(192 + rewardIndex * 712);
+
+    const globalConfig = new PublicKey(data.subarray(40, 72));
+    const rewardMint = new PublicKey(rewardInfo.subarray(0, 32));
+
+    return {
+        globalConfig,
+        rewardMint,
+        rewardsVault: new PublicKey(rewardInfo.subarray(120, 152)),
+        rewardsTreasuryVault: PublicKey.findProgramAddressSync([Buffer.from("tvault"), globalConfig.toBuffer(), rewardMint.toBuffer()], K_FARMS_PROGRAM_ID)[0],
+        farmVaultsAuthority: PublicKey.findProgramAddressSync([Buffer.from("authority"), farm.toBuffer()], K_FARMS_PROGRAM_ID)[0],
+    };
+}
 
-    // Check if deposits and borrows are non-zero
-    const hasCollateral = currentTotalDeposit.gt(0);
-    const hasBorrows = currentTotalBorrow.gt(0);
-    
-    // Create flags based on whether values are non-zero
-    let flags = 0;
-    if (hasCollateral) {
-        flags |= FLAG_HAS_COLLATERAL;
-    }
-    if (hasBorrows) {
-        flags |= FLAG_HAS_BORROWS;
-    }
+// Number of initialized rewards of the farm, `harvest` wants the accounts of every one of them
+export async function farmRewardCount(connection: Connection, farm: PublicKey) {
+    const { data } = await connection.getAccountInfo(farm);
+
+    return Number(data.readBigUInt64LE(192 + 10 * 712));
+}
+
+// Every reserve the obligation uses, deposits first and borrows second, in the order the program expects
+// them at the start of the remaining accounts (matching kamino/obligation.rs)
+export async function obligationReserveAccounts(connection: Connection, obligationAddress: PublicKey): Promise<AccountMeta[]> {
+    const { data } = await connection.getAccountInfo(obligationAddress);
 
-    return flags;
+    const deposits = [...Array(8).keys()].map((i) => new PublicKey(data.subarray(96 + i * 136, 128 + i * 136)));
+    const borrows = [...Array(5).keys()].map((i) => new PublicKey(data.subarray(1208 + i * 200, 1240 + i * 200)));
+
+    return [...deposits, ...borrows]
+        .filter((reserve) => !reserve.equals(PublicKey.default))
+        .map((reserve) => ({ pubkey: reserve, isSigner: false, isWritable: true }));
+}
+
+// Deposited and borrowed market values of the obligation, as Kamino scaled fractions (60 fractional bits),
+// and its LTV as of the last refresh
+export async function obligationValues(connection: Connection, obligationAddress: PublicKey) {
+    const { data } = await connection.getAccountInfo(obligationAddress);
+
+    const debtValueSf = new BN(data.subarray(2208, 2224), "le");
+    const allowedBorrowValueSf = new BN(data.subarray(2240, 2256), "le");
+
+    return {
+        depositedValueSf: new BN(data.subarray(1192, 1208), "le"),
+        borrowedValueSf: new BN(data.subarray(2224, 2240), "le"),
+        // Borrow factor adjusted debt over the allowed borrow value, like `Obligation::ltv_bps`
+        ltvBps: debtValueSf.isZero() ? 0 : debtValueSf.muln(10_000).div(allowedBorrowValueSf).toNumber(),
+    };
 }
 
 export async function calcuateRepaymentAmount(connection: Connection, obligationAddress: PublicKey) {
@@ -113,4 +144,33 @@ export async function calcuateRepaymentAmount(connection: Connection, obligation
         .toDecimalPlaces(0, Decimal.ROUND_CEIL);
 
     return repayAmountInUnits;
-}
\ No newline at end of file
+}
+const FLASH_BORROW_RESERVE_LIQUIDITY_DISCRIMINATOR = Buffer.from([135, 231, 52, 167, 7, 52, 212, 193]);
+const FLASH_REPAY_RESERVE_LIQUIDITY_DISCRIMINATOR = Buffer.from([185, 117, 0, 203, 96, 245, 180, 186]);
+
+const flashLoanKeys = (user: PublicKey, userLiquidity: PublicKey, reserveLiquidity: PublicKey) => [
+    { pubkey: user, isSigner: true, isWritable: false },                        // user_transfer_authority
+    { pubkey: LENDING_MARKET_AUTH, isSigner: false, isWritable: false },        // lending_market_authority
+    { pubkey: LENDING_MARKET, isSigner: false, isWritable: false },             // lending_market
+    { pubkey: USDC_RESERVE, isSigner: false, isWritable: true },                // reserve
+    { pubkey: new PublicKey("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v"), isSigner: false, isWritable: false }, // reserve_liquidity_mint
+    { pubkey: reserveLiquidity, isSigner: false, isWritable: true },            // reserve source/destination liquidity
+    { pubkey: userLiquidity, isSigner: false, isWritable: true },               // user destination/source liquidity
+    { pubkey: USDC_FEE_RECEIVER, isSigner: false, isWritable: true },           // reserve_liquidity_fee_receiver
+    { pubkey: K_LEND_PROGRAM_ID, isSigner: false, isWritable: false },          // [optional] referrer_token_state
+    { pubkey: K_LEND_PROGRAM_ID, isSigner: false, isWritable: false },          // [optional] referrer_account
+    { pubkey: SYSVAR_INSTRUCTIONS_PUBKEY, isSigner: false, isWritable: false }, // sysvar_info
+    { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },           // token_program
+];
+
+export const flashBorrowUsdcIx = (user: PublicKey, userDestinationLiquidity: PublicKey, amount: BN) => new TransactionInstruction({
+    programId: K_LEND_PROGRAM_ID,
+    keys: flashLoanKeys(user, userDestinationLiquidity, USDC_SUPPLY_VAULT),
+    data: Buffer.concat([FLASH_BORROW_RESERVE_LIQUIDITY_DISCRIMINATOR, amount.toArrayLike(Buffer, "le", 8)]),
+});
+
+export const flashRepayUsdcIx = (user: PublicKey, userSourceLiquidity: PublicKey, amount: BN, borrowInstructionIndex: number) => new TransactionInstruction({
+    programId: K_LEND_PROGRAM_ID,
+    keys: flashLoanKeys(user, userSourceLiquidity, USDC_SUPPLY_VAULT),
+    data: Buffer.concat([FLASH_REPAY_RESERVE_LIQUIDITY_DISCRIMINATOR, amount.toArrayLike(Buffer, "le", 8), Buffer.from([borrowInstructionIndex])]),
+});